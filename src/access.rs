@@ -0,0 +1,170 @@
+use std::ops::Range;
+
+use alloy::primitives::U256;
+
+use crate::state::SlotStatus;
+
+/// Memory an operation reads and writes, as exact byte ranges
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryAccess {
+    pub reads: Vec<Range<usize>>,
+    pub writes: Vec<Range<usize>>,
+}
+
+/// The n-th stack item from the top, zero past the bottom of the stack
+pub(crate) fn arg(stack: &[U256], n: usize) -> U256 {
+    stack.len().checked_sub(n + 1).map_or(U256::ZERO, |index| stack[index])
+}
+
+pub(crate) fn arg_usize(stack: &[U256], n: usize) -> usize {
+    arg(stack, n).saturating_to::<usize>()
+}
+
+/// Stack position of the calldata offset of a CALL family operation, followed by the calldata
+/// size and the return buffer offset and size. CALL and CALLCODE take a value argument before
+/// the memory ranges
+pub(crate) fn call_args(op: &str) -> usize {
+    match op {
+        "CALL" | "CALLCODE" => 3,
+        _ => 2,
+    }
+}
+
+/// Byte range starting at the offset found at `offset` on the stack, zero-sized ranges are
+/// dropped since they neither touch nor expand memory
+fn range(stack: &[U256], offset: usize, size: usize) -> Option<Range<usize>> {
+    let size = arg_usize(stack, size);
+    let start = arg_usize(stack, offset);
+    (size > 0).then(|| start..start.saturating_add(size))
+}
+
+fn fixed_range(stack: &[U256], offset: usize, size: usize) -> Range<usize> {
+    let start = arg_usize(stack, offset);
+    start..start.saturating_add(size)
+}
+
+impl MemoryAccess {
+    /// Access of an operation given its stack before execution, top of the stack last.
+    ///
+    /// The return buffer of the CALL family is counted as written by the call itself, even
+    /// though it is only filled once the callee returns. MSIZE is treated as a read of the
+    /// whole memory, which is `memory_size` bytes long.
+    pub fn from_operation(op: &str, stack: &[U256], memory_size: usize) -> Self {
+        let (reads, writes) = match op {
+            "MLOAD" => (vec![Some(fixed_range(stack, 0, 32))], vec![]),
+            "MSTORE" => (vec![], vec![Some(fixed_range(stack, 0, 32))]),
+            "MSTORE8" => (vec![], vec![Some(fixed_range(stack, 0, 1))]),
+            "MSIZE" => (vec![(memory_size > 0).then_some(0..memory_size)], vec![]),
+            "SHA3" | "KECCAK256" | "RETURN" | "REVERT" | "LOG0" | "LOG1" | "LOG2" | "LOG3"
+            | "LOG4" => (vec![range(stack, 0, 1)], vec![]),
+            "CALLDATACOPY" | "CODECOPY" | "RETURNDATACOPY" => (vec![], vec![range(stack, 0, 2)]),
+            "EXTCODECOPY" => (vec![], vec![range(stack, 1, 3)]),
+            "MCOPY" => (vec![range(stack, 1, 2)], vec![range(stack, 0, 2)]),
+            "CREATE" | "CREATE2" => (vec![range(stack, 1, 2)], vec![]),
+            "CALL" | "CALLCODE" | "DELEGATECALL" | "STATICCALL" => {
+                let args = call_args(op);
+                (vec![range(stack, args, args + 1)], vec![range(stack, args + 2, args + 3)])
+            }
+            _ => (vec![], vec![]),
+        };
+
+        Self {
+            reads: reads.into_iter().flatten().collect(),
+            writes: writes.into_iter().flatten().collect(),
+        }
+    }
+
+    /// Number of distinct slots the operation reads or writes
+    pub fn slot_count(&self) -> usize {
+        let mut touched: Vec<usize> =
+            self.reads.iter().chain(&self.writes).flat_map(slots).collect();
+        touched.sort_unstable();
+        touched.dedup();
+        touched.len()
+    }
+
+    /// Status of the slots the operation touches, writes take precedence over reads
+    pub fn status(&self) -> SlotStatus {
+        if !self.writes.is_empty() {
+            SlotStatus::Writing
+        } else if !self.reads.is_empty() {
+            SlotStatus::Reading
+        } else {
+            SlotStatus::Empty
+        }
+    }
+}
+
+/// Slots a byte range overlaps, an unaligned range touches every word it crosses
+pub fn slots(range: &Range<usize>) -> Range<usize> {
+    if range.is_empty() {
+        return 0..0;
+    }
+    range.start / 32..range.end.div_ceil(32)
+}
+
+/// Status an opcode gives to the memory it touches, regardless of its arguments
+pub fn status(op: &str) -> SlotStatus {
+    match op {
+        "MSTORE" | "MSTORE8" | "CALLDATACOPY" | "CODECOPY" | "RETURNDATACOPY" | "EXTCODECOPY"
+        | "MCOPY" | "CALL" | "CALLCODE" | "DELEGATECALL" | "STATICCALL" => SlotStatus::Writing,
+        "MLOAD" | "MSIZE" | "SHA3" | "KECCAK256" | "RETURN" | "REVERT" | "LOG0" | "LOG1"
+        | "LOG2" | "LOG3" | "LOG4" | "CREATE" | "CREATE2" => SlotStatus::Reading,
+        _ => SlotStatus::Empty,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Access of an operation given its stack, top of the stack last
+    fn access(op: &str, stack: &[u64]) -> MemoryAccess {
+        let stack: Vec<U256> = stack.iter().map(|value| U256::from(*value)).collect();
+        MemoryAccess::from_operation(op, &stack, 0)
+    }
+
+    #[test]
+    fn mstore8_writes_a_single_byte() {
+        let access = access("MSTORE8", &[0xff, 33]);
+        assert_eq!(access.writes, vec![33..34]);
+        assert_eq!(slots(&access.writes[0]), 1..2);
+        assert_eq!(access.status(), SlotStatus::Writing);
+    }
+
+    #[test]
+    fn mcopy_reads_and_writes_overlapping_ranges() {
+        let access = access("MCOPY", &[64, 16, 0]);
+        assert_eq!((access.reads.clone(), access.writes.clone()), (vec![16..80], vec![0..64]));
+        // Both ranges share the first two words
+        assert_eq!(access.slot_count(), 3);
+        assert_eq!(access.status(), SlotStatus::Writing);
+    }
+
+    #[test]
+    fn zero_length_copies_touch_nothing() {
+        let access = access("CALLDATACOPY", &[0, 4, 32]);
+        assert_eq!(access, MemoryAccess::default());
+        assert_eq!(access.status(), SlotStatus::Empty);
+        assert_eq!(status("CALLDATACOPY"), SlotStatus::Writing);
+        assert_eq!(slots(&(40..40)), 0..0);
+    }
+
+    #[test]
+    fn calls_with_a_value_take_their_ranges_one_item_deeper() {
+        let call = access("CALL", &[32, 0x40, 4, 0x1c, 1, 0xaa, 1000]);
+        let static_call = access("STATICCALL", &[32, 0x40, 4, 0x1c, 0xaa, 1000]);
+        for access in [call, static_call] {
+            assert_eq!(access.reads, vec![0x1c..0x20]);
+            assert_eq!(access.writes, vec![0x40..0x60]);
+        }
+    }
+
+    #[test]
+    fn logs_read_their_data() {
+        let access = access("LOG2", &[2, 1, 10, 5]);
+        assert_eq!((access.reads.clone(), access.writes.clone()), (vec![5..15], vec![]));
+        assert_eq!(slots(&access.reads[0]), 0..1);
+        assert_eq!(access.status(), SlotStatus::Reading);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     render::RenderData,
-    state::AppState,
+    state::{AppMode, AppState, FrameView, Panel},
     tui::{self, Event},
 };
 
@@ -47,6 +47,8 @@ impl App {
         // Main loop begins here
         loop {
             app_state.run(self.iteration, self.forward, self.pause).await?;
+            // A breakpoint pauses playback from inside the state
+            self.pause = app_state.pause;
 
             if let Some(evt) = tui.next().await {
                 match evt {
@@ -56,7 +58,7 @@ impl App {
                         })?;
                     }
                     Event::Key(key) => {
-                        self.handle_event(key, app_state);
+                        self.handle_event(key, app_state)?;
                     }
                     _ => {}
                 }
@@ -75,7 +77,22 @@ impl App {
         frame.render_stateful_widget(self, frame.size(), state);
     }
 
-    fn handle_event(&mut self, key: KeyEvent, state: &mut AppState) {
+    fn handle_event(&mut self, key: KeyEvent, state: &mut AppState) -> Result<(), eyre::Error> {
+        // The outcome of the last jump stays on screen until the next key
+        state.message = None;
+        if state.command.is_some() {
+            self.handle_command_event(key, state);
+            return Ok(());
+        }
+        if state.panel == Some(Panel::CallTree) && self.handle_call_tree_event(key, state)? {
+            return Ok(());
+        }
+        let stack_shown =
+            state.panel == Some(Panel::Stack) || matches!(state.mode, AppMode::Versus);
+        if stack_shown && self.handle_stack_event(key, state) {
+            return Ok(());
+        }
+
         match key.code {
             Char(c) => {
                 match c.to_ascii_lowercase() {
@@ -90,6 +107,47 @@ impl App {
                     's' => state.table_beginning_index += 1,
                     'h' => state.help = !state.help,
                     'f' => state.display_memory_data = !state.display_memory_data,
+                    'p' => {
+                        // Stop at the outermost frame of the deepest call stack
+                        let callers = state
+                            .transaction_states
+                            .iter()
+                            .map(|transaction| transaction.frames.len().saturating_sub(1))
+                            .max()
+                            .unwrap_or_default();
+                        state.frame_view = match state.frame_view {
+                            FrameView::Caller(offset) => {
+                                FrameView::Caller((offset + 1).min(callers))
+                            }
+                            _ => FrameView::Caller(1.min(callers)),
+                        };
+                    }
+                    ':' => state.command = Some(String::new()),
+                    'c' => state.frame_view = FrameView::Current,
+                    't' if matches!(state.mode, AppMode::Normal) => {
+                        state.toggle_panel(Panel::CallTree)
+                    }
+                    'o' if matches!(state.mode, AppMode::Normal) => {
+                        state.toggle_panel(Panel::Storage)
+                    }
+                    'e' if matches!(state.mode, AppMode::Normal) => {
+                        state.toggle_panel(Panel::TransientStorage)
+                    }
+                    'k' if matches!(state.mode, AppMode::Normal) => {
+                        state.toggle_panel(Panel::Stack)
+                    }
+                    'r' if matches!(state.mode, AppMode::Normal) => {
+                        state.toggle_panel(Panel::ReturnData)
+                    }
+                    'd' if matches!(state.mode, AppMode::Normal) => {
+                        state.toggle_panel(Panel::Calldata)
+                    }
+                    'a' => {
+                        state.frame_view = match state.frame_view {
+                            FrameView::All => FrameView::Current,
+                            _ => FrameView::All,
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -103,6 +161,67 @@ impl App {
             }
             _ => {}
         }
+        Ok(())
+    }
+
+    /// Typing in the command prompt, playback pauses once a command runs
+    fn handle_command_event(&mut self, key: KeyEvent, state: &mut AppState) {
+        let Some(command) = &mut state.command else {
+            return;
+        };
+        match key.code {
+            Char(c) => command.push(c),
+            crossterm::event::KeyCode::Backspace => {
+                command.pop();
+            }
+            crossterm::event::KeyCode::Esc => state.command = None,
+            crossterm::event::KeyCode::Enter => {
+                let command = state.command.take().unwrap_or_default();
+                state.message = match state.execute(&command) {
+                    Ok(message) => message,
+                    Err(error) => Some(error.to_string()),
+                };
+                self.pause = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Scrolling keys of the stack panel, returns whether the key was used
+    fn handle_stack_event(&mut self, key: KeyEvent, state: &mut AppState) -> bool {
+        match key.code {
+            crossterm::event::KeyCode::Up => {
+                state.stack_vertical_scroll = state.stack_vertical_scroll.saturating_sub(1)
+            }
+            crossterm::event::KeyCode::Down => state.stack_vertical_scroll += 1,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Navigation keys of the call tree panel, returns whether the key was used
+    fn handle_call_tree_event(
+        &mut self,
+        key: KeyEvent,
+        state: &mut AppState,
+    ) -> Result<bool, eyre::Error> {
+        let Some(transaction_state) = state.transaction_states.first_mut() else {
+            return Ok(false);
+        };
+        match key.code {
+            crossterm::event::KeyCode::Up => transaction_state.move_call_cursor(-1),
+            crossterm::event::KeyCode::Down => transaction_state.move_call_cursor(1),
+            // Left and Right keep stepping playback while the panel is open
+            Char('-') => transaction_state.collapse_call(),
+            Char('+' | '=') => transaction_state.expand_call(),
+            crossterm::event::KeyCode::Enter => {
+                transaction_state.enter_call()?;
+                // Stay on the first operation of the call instead of playing past it
+                self.pause = true;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
     }
 }
 
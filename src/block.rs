@@ -0,0 +1,136 @@
+use alloy::{
+    primitives::hex,
+    providers::{Provider, RootProvider},
+    rpc::types::eth::{BlockNumberOrTag, Transaction},
+    transports::BoxTransport,
+};
+use color_eyre::eyre::{self, eyre};
+
+use crate::{
+    cache::{self, CacheKey, CacheMode},
+    provider::RpcConfig,
+    source::{self, TraceBackend, TraceData},
+};
+
+/// A transaction of a block together with what its receipt says about it
+#[derive(Debug, Clone)]
+pub struct BlockTransaction {
+    pub transaction: Transaction,
+    /// `None` when the node does not serve `eth_getBlockReceipts`
+    pub gas_used: Option<u128>,
+    pub success: Option<bool>,
+}
+
+impl BlockTransaction {
+    /// Function selector of the calldata, if it is long enough to have one
+    pub fn selector(&self) -> Option<String> {
+        (self.transaction.to.is_some() && self.transaction.input.len() >= 4)
+            .then(|| format!("0x{}", hex::encode(&self.transaction.input[..4])))
+    }
+}
+
+/// The transactions of a block, in execution order
+#[derive(Debug, Clone)]
+pub struct BlockData {
+    pub number: u64,
+    pub transactions: Vec<BlockTransaction>,
+}
+
+/// Fetches a block with its transactions and their receipts
+pub async fn fetch_block(
+    rpc: &RpcConfig,
+    provider: &RootProvider<BoxTransport>,
+    block: BlockNumberOrTag,
+) -> Result<BlockData, eyre::Error> {
+    let result = rpc
+        .request("eth_getBlockByNumber", || provider.get_block_by_number(block, true))
+        .await?
+        .ok_or_else(|| eyre!("Block {} was not found", block))?;
+    let number = result.header.number.ok_or_else(|| eyre!("Block {} is still pending", block))?;
+    let transactions = result
+        .transactions
+        .as_transactions()
+        .ok_or_else(|| eyre!("The node returned no transaction bodies for block {}", number))?
+        .to_vec();
+
+    // The picker still works without receipts, it just cannot show the gas used
+    let receipts = match rpc
+        .request("eth_getBlockReceipts", || {
+            provider.get_block_receipts(BlockNumberOrTag::Number(number))
+        })
+        .await
+    {
+        Ok(receipts) => receipts.unwrap_or_default(),
+        Err(error) => {
+            tracing::warn!("Failed to fetch the receipts of block {}: {}", number, error);
+            vec![]
+        }
+    };
+
+    let transactions = transactions
+        .into_iter()
+        .map(|transaction| {
+            let receipt =
+                receipts.iter().find(|receipt| receipt.transaction_hash == transaction.hash);
+            BlockTransaction {
+                gas_used: receipt.map(|receipt| receipt.gas_used),
+                success: receipt.map(|receipt| receipt.status()),
+                transaction,
+            }
+        })
+        .collect();
+
+    Ok(BlockData { number, transactions })
+}
+
+/// Traces every transaction of a block with a single `debug_traceBlockByNumber` request and
+/// stores the traces in the cache, so opening another transaction of the block is instant
+pub async fn trace_block(
+    rpc: &RpcConfig,
+    provider: &RootProvider<BoxTransport>,
+    block: &BlockData,
+    cache_mode: CacheMode,
+) -> Result<Vec<TraceData>, eyre::Error> {
+    let chain_id = rpc.request("eth_chainId", || provider.get_chain_id()).await?;
+    let results: Vec<serde_json::Value> = rpc
+        .request("debug_traceBlockByNumber", || {
+            provider.client().request(
+                "debug_traceBlockByNumber",
+                (BlockNumberOrTag::Number(block.number), source::tracing_options(rpc, true)),
+            )
+        })
+        .await?;
+
+    if results.len() != block.transactions.len() {
+        return Err(eyre!(
+            "debug_traceBlockByNumber returned {} traces for the {} transactions of block {}",
+            results.len(),
+            block.transactions.len(),
+            block.number
+        ));
+    }
+
+    results
+        .into_iter()
+        .zip(&block.transactions)
+        .map(|(result, block_transaction)| {
+            let (success, struct_logs) = source::parse_trace(result)?;
+            let transaction = block_transaction.transaction.clone();
+            let data = TraceData { transaction, success, struct_logs };
+            if cache_mode != CacheMode::Disabled {
+                let hash = data.transaction.hash;
+                let key = CacheKey {
+                    chain_id,
+                    transaction: hash,
+                    backend: TraceBackend::Debug,
+                    reconstruct_memory: false,
+                    hardfork: None,
+                };
+                if let Err(error) = cache::store(&key, &data) {
+                    tracing::warn!("Failed to cache the trace of {}: {}", hash, error);
+                }
+            }
+            Ok(data)
+        })
+        .collect()
+}
@@ -0,0 +1,94 @@
+use std::{fmt, str::FromStr};
+
+use alloy::primitives::Address;
+use color_eyre::eyre::{self, eyre};
+
+use crate::{number::parse_number, state::TransactionState};
+
+/// Condition that pauses playback on the first operation that meets it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    Opcode(String),
+    Pc(u64),
+    /// Code of this address runs, including the frames of calls to it
+    Address(Address),
+    Depth(u64),
+    /// Remaining gas drops below this amount
+    GasBelow(u64),
+    /// Memory of the frame grows past this many bytes
+    MemoryAbove(usize),
+}
+
+impl FromStr for Breakpoint {
+    type Err = eyre::Error;
+
+    /// Accepts `op <opcode>`, `pc <pc>`, `address <address>`, `depth <depth>`,
+    /// `gas below <gas>` and `memory above <bytes>`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.to_lowercase();
+        let words: Vec<&str> = text
+            .split_whitespace()
+            .filter(|word| !matches!(*word, "below" | "above" | "<" | ">"))
+            .collect();
+
+        match words.as_slice() {
+            ["op" | "opcode", opcode] => Ok(Breakpoint::Opcode(opcode.to_uppercase())),
+            ["pc", pc] => Ok(Breakpoint::Pc(parse_number(pc)?)),
+            ["address", address] => Ok(Breakpoint::Address(address.parse()?)),
+            ["depth", depth] => Ok(Breakpoint::Depth(parse_number(depth)?)),
+            ["gas", gas] => Ok(Breakpoint::GasBelow(parse_number(gas)?)),
+            ["memory", size] => Ok(Breakpoint::MemoryAbove(parse_number(size)? as usize)),
+            _ => Err(eyre!("Unknown breakpoint {:?}", text)),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Opcode(opcode) => write!(f, "op {}", opcode),
+            Breakpoint::Pc(pc) => write!(f, "pc {:#x}", pc),
+            Breakpoint::Address(address) => write!(f, "address {}", address),
+            Breakpoint::Depth(depth) => write!(f, "depth {}", depth),
+            Breakpoint::GasBelow(gas) => write!(f, "gas below {}", gas),
+            Breakpoint::MemoryAbove(size) => write!(f, "memory above {}", size),
+        }
+    }
+}
+
+impl Breakpoint {
+    /// Whether the given operation meets the condition while the previous step did not.
+    /// Opcodes and program counters fire on every matching operation, the other conditions
+    /// last for many operations and only fire when they start to hold
+    pub fn hit(
+        &self,
+        transaction_state: &TransactionState,
+        operation: usize,
+        previous: Option<usize>,
+    ) -> bool {
+        let held = match self {
+            Breakpoint::Opcode(_) | Breakpoint::Pc(_) => false,
+            _ => previous.is_some_and(|previous| self.holds(transaction_state, previous)),
+        };
+        !held && self.holds(transaction_state, operation)
+    }
+
+    /// Whether the given operation of the transaction meets the condition
+    fn holds(&self, transaction_state: &TransactionState, operation: usize) -> bool {
+        let Some(step) = transaction_state.raw_data.get(operation) else {
+            return false;
+        };
+        match self {
+            Breakpoint::Opcode(opcode) => step.op == *opcode,
+            Breakpoint::Pc(pc) => step.pc == *pc,
+            Breakpoint::Address(address) => {
+                let call_tree = &transaction_state.call_tree;
+                let node = call_tree.nodes.get(call_tree.node_at(operation, step.depth));
+                node.and_then(|node| node.address) == Some(*address)
+            }
+            Breakpoint::Depth(depth) => step.depth == *depth,
+            Breakpoint::GasBelow(gas) => step.gas < *gas,
+            Breakpoint::MemoryAbove(size) => step.memory.as_ref().map_or(0, Vec::len) * 32 > *size,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use alloy::primitives::TxHash;
+use clap::{builder::PossibleValue, ValueEnum};
+use color_eyre::eyre;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{
+    log::get_data_dir,
+    replay::Hardfork,
+    source::{TraceBackend, TraceData},
+};
+
+/// Extension of the compressed cache entries
+static CACHE_EXTENSION: &str = "json.gz";
+
+/// How the on-disk trace cache is used when fetching a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CacheMode {
+    /// Read from the cache and store fetched traces in it
+    #[default]
+    Enabled,
+    /// Ignore cached traces but store the freshly fetched ones
+    Refresh,
+    /// Neither read from nor write to the cache
+    Disabled,
+}
+
+/// Identifies a cached trace. Backends, memory modes and hardforks produce different struct logs
+/// for the same transaction, so each of them gets its own entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheKey {
+    pub chain_id: u64,
+    pub transaction: TxHash,
+    pub backend: TraceBackend,
+    /// Memory was rebuilt locally instead of fetched
+    pub reconstruct_memory: bool,
+    /// Hardfork the replay tracer was forced to run with, `None` for the one of the block
+    pub hardfork: Option<Hardfork>,
+}
+
+impl CacheKey {
+    /// Backend, memory mode and hardfork as they appear in the name of the entry
+    fn variant(&self) -> String {
+        let name = |value: Option<PossibleValue>| {
+            value.map_or(String::new(), |value| value.get_name().to_string())
+        };
+        let mut variant = name(self.backend.to_possible_value());
+        if self.reconstruct_memory {
+            variant.push_str("-no-memory");
+        }
+        if let Some(hardfork) = self.hardfork {
+            variant = format!("{}-{}", variant, name(hardfork.to_possible_value()));
+        }
+        variant
+    }
+}
+
+/// A trace stored in the cache
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub chain_id: u64,
+    pub transaction: String,
+    /// Backend and memory mode of the trace, empty for entries written before they were part
+    /// of the key
+    pub variant: String,
+    pub size: u64,
+}
+
+pub fn cache_directory() -> PathBuf {
+    get_data_dir().join("cache")
+}
+
+fn entry_path(key: &CacheKey) -> PathBuf {
+    cache_directory()
+        .join(key.chain_id.to_string())
+        .join(format!("{}.{}.{}", key.transaction, key.variant(), CACHE_EXTENSION))
+}
+
+/// Returns the cached trace of a transaction, if there is one
+pub fn load(key: &CacheKey) -> Option<TraceData> {
+    let transaction = key.transaction;
+    let file = File::open(entry_path(key)).ok()?;
+    match serde_json::from_reader(BufReader::new(GzDecoder::new(file))) {
+        Ok(data) => Some(data),
+        Err(error) => {
+            tracing::warn!("Ignoring unreadable cache entry for {}: {}", transaction, error);
+            None
+        }
+    }
+}
+
+/// Compresses and stores the trace of a transaction
+pub fn store(key: &CacheKey, data: &TraceData) -> Result<(), eyre::Error> {
+    let path = entry_path(key);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Write to a temporary file first so an interrupted run never leaves a truncated entry behind
+    let temporary_path = path.with_extension("tmp");
+    let mut encoder =
+        GzEncoder::new(BufWriter::new(File::create(&temporary_path)?), Compression::default());
+    serde_json::to_writer(&mut encoder, data)?;
+    encoder.finish()?;
+    std::fs::rename(temporary_path, path)?;
+    Ok(())
+}
+
+/// Lists every cached trace
+pub fn list() -> Result<Vec<CacheEntry>, eyre::Error> {
+    let mut entries = vec![];
+    let directory = cache_directory();
+    if !directory.exists() {
+        return Ok(entries);
+    }
+
+    for chain_directory in std::fs::read_dir(directory)? {
+        let chain_directory = chain_directory?;
+        let Ok(chain_id) = chain_directory.file_name().to_string_lossy().parse::<u64>() else {
+            continue;
+        };
+        for file in std::fs::read_dir(chain_directory.path())? {
+            let file = file?;
+            let name = file.file_name().to_string_lossy().to_string();
+            if let Some(stem) = name.strip_suffix(&format!(".{}", CACHE_EXTENSION)) {
+                let (transaction, variant) = stem.split_once('.').unwrap_or((stem, ""));
+                entries.push(CacheEntry {
+                    chain_id,
+                    transaction: transaction.to_string(),
+                    variant: variant.to_string(),
+                    size: file.metadata()?.len(),
+                });
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| {
+        (a.chain_id, &a.transaction, &a.variant).cmp(&(b.chain_id, &b.transaction, &b.variant))
+    });
+    Ok(entries)
+}
+
+/// Removes every cached trace and returns the number of removed entries
+pub fn clear() -> Result<usize, eyre::Error> {
+    let removed = list()?.len();
+    let directory = cache_directory();
+    if directory.exists() {
+        std::fs::remove_dir_all(directory)?;
+    }
+    Ok(removed)
+}
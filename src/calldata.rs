@@ -0,0 +1,253 @@
+use std::{ops::Range, path::PathBuf};
+
+use alloy::{
+    dyn_abi::{DynSolValue, JsonAbiExt},
+    json_abi::{Function, JsonAbi},
+    primitives::{hex, Bytes, U256},
+};
+use color_eyre::eyre::{self, eyre};
+
+use crate::{
+    access::{arg_usize, call_args},
+    calltree::{memory_bytes, CallKind},
+    state::TransactionState,
+};
+
+/// Functions of the ABIs passed with `--abi`, looked up by selector
+#[derive(Debug, Clone, Default)]
+pub struct Abi {
+    pub functions: Vec<Function>,
+}
+
+/// An argument of a decoded call and the bytes of the calldata encoding it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Argument {
+    pub name: String,
+    pub ty: String,
+    pub value: String,
+    /// Head of the argument, the whole encoding for static types
+    pub head: Range<usize>,
+    /// Data the head points at for dynamic types
+    pub tail: Option<Range<usize>>,
+}
+
+impl Argument {
+    /// Whether any byte of the argument lies in the given range of the calldata
+    pub fn overlaps(&self, range: &Range<usize>) -> bool {
+        let overlaps = |bytes: &Range<usize>| bytes.start < range.end && range.start < bytes.end;
+        overlaps(&self.head) || self.tail.as_ref().is_some_and(overlaps)
+    }
+}
+
+/// A call decoded with one of the known functions
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedCall {
+    pub signature: String,
+    pub arguments: Vec<Argument>,
+}
+
+impl DecodedCall {
+    /// Names of the arguments encoded in the given range of the calldata
+    pub fn arguments_in(&self, range: &Range<usize>) -> Vec<&str> {
+        self.arguments
+            .iter()
+            .filter(|argument| argument.overlaps(range))
+            .map(|argument| argument.name.as_str())
+            .collect()
+    }
+}
+
+impl Abi {
+    /// Reads ABI JSON files and Foundry or Hardhat artifacts, which keep the ABI under `abi`
+    pub fn load(paths: &[PathBuf]) -> Result<Self, eyre::Error> {
+        let mut functions = vec![];
+        for path in paths {
+            let content = std::fs::read_to_string(path)
+                .map_err(|error| eyre!("Failed to read {}: {}", path.display(), error))?;
+            let mut json: serde_json::Value = serde_json::from_str(&content)?;
+            if let Some(abi) = json.get_mut("abi") {
+                json = abi.take();
+            }
+            let abi: JsonAbi = serde_json::from_value(json)
+                .map_err(|error| eyre!("{} is not an ABI: {}", path.display(), error))?;
+            functions.extend(abi.functions().cloned());
+        }
+        Ok(Self { functions })
+    }
+
+    /// Decodes calldata with the function matching its selector
+    pub fn decode(&self, calldata: &[u8]) -> Option<DecodedCall> {
+        let selector = calldata.get(..4)?;
+        let function =
+            self.functions.iter().find(|function| function.selector().as_slice() == selector)?;
+        let values = function.abi_decode_input(&calldata[4..], false).ok()?;
+
+        let mut head = 4;
+        let arguments = function
+            .inputs
+            .iter()
+            .zip(values)
+            .enumerate()
+            .map(|(index, (param, value))| {
+                let size = head_size(&value);
+                let tail = is_dynamic(&value).then(|| {
+                    // Offsets of dynamic arguments count from the end of the selector
+                    let offset = calldata
+                        .get(head..head + 32)
+                        .map_or(0, |word| U256::from_be_slice(word).saturating_to::<usize>());
+                    let start = offset.saturating_add(4);
+                    start..start.saturating_add(tail_size(&value))
+                });
+                let argument = Argument {
+                    name: match param.name.is_empty() {
+                        true => format!("arg{}", index),
+                        false => param.name.clone(),
+                    },
+                    ty: param.ty.clone(),
+                    value: format_value(&value),
+                    head: head..head + size,
+                    tail,
+                };
+                head += size;
+                argument
+            })
+            .collect();
+        Some(DecodedCall { signature: function.signature(), arguments })
+    }
+}
+
+fn is_dynamic(value: &DynSolValue) -> bool {
+    match value {
+        DynSolValue::Bytes(_) | DynSolValue::String(_) | DynSolValue::Array(_) => true,
+        DynSolValue::FixedArray(items) | DynSolValue::Tuple(items) => items.iter().any(is_dynamic),
+        _ => false,
+    }
+}
+
+/// Bytes a value takes in the head of its encoding
+fn head_size(value: &DynSolValue) -> usize {
+    match value {
+        _ if is_dynamic(value) => 32,
+        DynSolValue::FixedArray(items) | DynSolValue::Tuple(items) => {
+            items.iter().map(head_size).sum()
+        }
+        _ => 32,
+    }
+}
+
+/// Bytes the data of a dynamic value takes after the heads
+fn tail_size(value: &DynSolValue) -> usize {
+    let encoded = |items: &[DynSolValue]| -> usize {
+        items.iter().map(|item| head_size(item) + tail_size(item)).sum()
+    };
+    match value {
+        DynSolValue::Bytes(bytes) => 32 + bytes.len().div_ceil(32) * 32,
+        DynSolValue::String(text) => 32 + text.len().div_ceil(32) * 32,
+        DynSolValue::Array(items) => 32 + encoded(items),
+        DynSolValue::FixedArray(items) | DynSolValue::Tuple(items) if is_dynamic(value) => {
+            encoded(items)
+        }
+        _ => 0,
+    }
+}
+
+fn format_value(value: &DynSolValue) -> String {
+    let list = |items: &[DynSolValue]| items.iter().map(format_value).collect::<Vec<_>>();
+    match value {
+        DynSolValue::Bool(value) => value.to_string(),
+        DynSolValue::Int(value, _) => value.to_string(),
+        DynSolValue::Uint(value, _) => value.to_string(),
+        DynSolValue::Address(address) => address.to_string(),
+        DynSolValue::FixedBytes(word, size) => format!("0x{}", hex::encode(&word[..*size])),
+        DynSolValue::Bytes(bytes) => format!("0x{}", hex::encode(bytes)),
+        DynSolValue::String(text) => format!("{:?}", text),
+        DynSolValue::Array(items) | DynSolValue::FixedArray(items) => {
+            format!("[{}]", list(items).join(", "))
+        }
+        DynSolValue::Tuple(items) => format!("({})", list(items).join(", ")),
+        _ => format!("{:?}", value),
+    }
+}
+
+/// Calldata the frame of the given call tree node was entered with, the init code for contract
+/// creations
+pub fn calldata(transaction_state: &TransactionState, node: usize) -> Option<Bytes> {
+    let call = transaction_state.call_tree.nodes.get(node)?;
+    let steps = &transaction_state.raw_data;
+    if call.kind == CallKind::Transaction {
+        return Some(transaction_state.transaction.input.clone());
+    }
+    // Entered calls start right after the operation making them
+    let calling = match steps.get(call.first_step)?.depth == call.depth {
+        true => call.first_step.checked_sub(1)?,
+        false => call.first_step,
+    };
+    let step = steps.get(calling)?;
+    let stack = step.stack.as_deref().unwrap_or_default();
+    let offset = match call.kind.is_create() {
+        true => 1,
+        false => call_args(&step.op),
+    };
+    let (offset, size) = (arg_usize(stack, offset), arg_usize(stack, offset + 1));
+    let memory = step.memory.as_deref().unwrap_or_default();
+    Some(Bytes::from(memory_bytes(memory, offset, size)))
+}
+
+/// Range of the calldata the given CALLDATALOAD or CALLDATACOPY reads
+pub fn read_range(op: &str, stack: &[U256]) -> Option<Range<usize>> {
+    let arg = |n: usize| arg_usize(stack, n);
+    match op {
+        "CALLDATALOAD" => Some(arg(0)..arg(0).saturating_add(32)),
+        "CALLDATACOPY" => Some(arg(1)..arg(1).saturating_add(arg(2))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn calldata_ranges_map_to_named_arguments() {
+        let abi: JsonAbi = serde_json::from_value(json!([{
+            "type": "function",
+            "name": "f",
+            "inputs": [
+                { "name": "amount", "type": "uint256" },
+                { "name": "data", "type": "bytes" }
+            ],
+            "outputs": [],
+            "stateMutability": "nonpayable"
+        }]))
+        .unwrap();
+        let abi = Abi { functions: abi.functions().cloned().collect() };
+        let mut calldata = abi.functions[0].selector().to_vec();
+        for word in [5, 0x40, 3] {
+            calldata.extend(U256::from(word).to_be_bytes::<32>());
+        }
+        calldata.extend(b"abc");
+        calldata.extend([0; 29]);
+
+        let decoded = abi.decode(&calldata).unwrap();
+        assert_eq!(decoded.signature, "f(uint256,bytes)");
+        assert_eq!(decoded.arguments[0].value, "5");
+        assert_eq!(decoded.arguments[1].head, 36..68);
+        assert_eq!(decoded.arguments[1].tail, Some(68..132));
+
+        // A CALLDATALOAD of the length of `data` and a copy of everything after the selector,
+        // top of the stack last
+        let load = read_range("CALLDATALOAD", &[U256::from(68)]).unwrap();
+        assert_eq!(decoded.arguments_in(&load), vec!["data"]);
+        let copy = read_range("CALLDATACOPY", &[128, 4, 0].map(U256::from)).unwrap();
+        assert_eq!(decoded.arguments_in(&copy), vec!["amount", "data"]);
+    }
+
+    #[test]
+    fn read_ranges_saturate() {
+        let stack = [U256::MAX, U256::MAX, U256::ZERO];
+        assert_eq!(read_range("CALLDATACOPY", &stack), Some(usize::MAX..usize::MAX));
+        assert_eq!(read_range("MLOAD", &stack), None);
+    }
+}
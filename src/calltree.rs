@@ -0,0 +1,245 @@
+use std::fmt;
+
+use alloy::{
+    primitives::{hex, Address, B256, U256},
+    rpc::types::{eth::Transaction, trace::geth::StructLog},
+};
+
+use crate::access::{arg, call_args};
+
+/// How a call frame was entered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallKind {
+    /// The transaction itself
+    Transaction,
+    Call,
+    StaticCall,
+    DelegateCall,
+    CallCode,
+    Create,
+    Create2,
+}
+
+impl CallKind {
+    fn from_opcode(op: &str) -> Option<Self> {
+        match op {
+            "CALL" => Some(CallKind::Call),
+            "STATICCALL" => Some(CallKind::StaticCall),
+            "DELEGATECALL" => Some(CallKind::DelegateCall),
+            "CALLCODE" => Some(CallKind::CallCode),
+            "CREATE" => Some(CallKind::Create),
+            "CREATE2" => Some(CallKind::Create2),
+            _ => None,
+        }
+    }
+
+    pub fn is_create(&self) -> bool {
+        matches!(self, CallKind::Create | CallKind::Create2)
+    }
+}
+
+impl fmt::Display for CallKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            CallKind::Transaction => "TX",
+            CallKind::Call => "CALL",
+            CallKind::StaticCall => "STATICCALL",
+            CallKind::DelegateCall => "DELEGATECALL",
+            CallKind::CallCode => "CALLCODE",
+            CallKind::Create => "CREATE",
+            CallKind::Create2 => "CREATE2",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// A call frame of the transaction
+#[derive(Debug, Clone)]
+pub struct CallNode {
+    pub kind: CallKind,
+    /// Call depth of the frame, 1 for the transaction itself
+    pub depth: u64,
+    /// Called address, or the created one once it is known
+    pub address: Option<Address>,
+    /// First four bytes of the calldata
+    pub selector: Option<String>,
+    /// Wei sent along, `None` for calls that cannot send value
+    pub value: Option<U256>,
+    /// Gas available to the frame
+    pub gas: u64,
+    pub success: bool,
+    /// First operation executed in the frame. Calls to precompiles and accounts without code
+    /// have no operations of their own, so this is the calling operation for them
+    pub first_step: usize,
+    /// Last operation executed in the frame
+    pub last_step: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+impl CallNode {
+    /// Whether the frame executed the given operation, including its sub calls
+    pub fn contains(&self, step: usize) -> bool {
+        (self.first_step..=self.last_step).contains(&step)
+    }
+}
+
+/// The call frames of a transaction, the transaction itself first
+#[derive(Debug, Clone, Default)]
+pub struct CallTree {
+    pub nodes: Vec<CallNode>,
+}
+
+/// Reads bytes from a memory snapshot made of 32-byte hex words, nothing for ranges that end
+/// past the address space
+pub fn memory_bytes(memory: &[String], offset: usize, size: usize) -> Vec<u8> {
+    let Some(end) = offset.checked_add(size) else {
+        return vec![];
+    };
+    let first_word = offset / 32;
+    let last_word = end.div_ceil(32);
+    let words = memory.get(first_word..last_word.min(memory.len())).unwrap_or_default();
+    let bytes = hex::decode(words.concat()).unwrap_or_default();
+    bytes.into_iter().skip(offset % 32).take(size).collect()
+}
+
+impl CallTree {
+    pub fn build(transaction: &Transaction, success: bool, steps: &[StructLog]) -> Self {
+        let selector = (transaction.to.is_some() && transaction.input.len() >= 4)
+            .then(|| format!("0x{}", hex::encode(&transaction.input[..4])));
+        let mut nodes = vec![CallNode {
+            kind: CallKind::Transaction,
+            depth: 1,
+            address: transaction.to,
+            selector,
+            value: Some(transaction.value),
+            gas: steps.first().map_or(0, |step| step.gas),
+            success,
+            first_step: 0,
+            last_step: steps.len().saturating_sub(1),
+            parent: None,
+            children: vec![],
+        }];
+        // Frames that were entered and did not return yet
+        let mut open = vec![0];
+
+        for (index, step) in steps.iter().enumerate() {
+            // A step at a lower depth is the caller resuming, with the result of the call on top
+            // of its stack
+            while open.len() > 1 && step.depth < nodes[*open.last().unwrap()].depth {
+                let node = &mut nodes[open.pop().unwrap()];
+                let result = step.stack.as_deref().map_or(U256::ZERO, |stack| arg(stack, 0));
+                node.last_step = index - 1;
+                node.success = result != U256::ZERO;
+                if node.kind.is_create() && node.success {
+                    node.address = Some(Address::from_word(B256::from(result)));
+                }
+            }
+
+            let Some(kind) = CallKind::from_opcode(&step.op) else {
+                continue;
+            };
+            let stack = step.stack.as_deref().unwrap_or_default();
+            let memory = step.memory.as_deref().unwrap_or_default();
+            let parent = *open.last().unwrap();
+            let next = steps.get(index + 1);
+            let entered = next.is_some_and(|next| next.depth > step.depth);
+
+            let (address, value, arguments) = match kind {
+                CallKind::Create | CallKind::Create2 => (None, Some(arg(stack, 0)), None),
+                _ => {
+                    let args = call_args(&step.op);
+                    let value =
+                        matches!(kind, CallKind::Call | CallKind::CallCode).then(|| arg(stack, 2));
+                    (Some(arg(stack, 1)), value, Some((arg(stack, args), arg(stack, args + 1))))
+                }
+            };
+            let selector = arguments
+                .filter(|(_, size)| *size >= U256::from(4))
+                .map(|(offset, _)| memory_bytes(memory, offset.saturating_to(), 4))
+                .filter(|selector| selector.len() == 4)
+                .map(|selector| format!("0x{}", hex::encode(selector)));
+            // Calls without steps of their own are resolved by the result the next step sees,
+            // the others once their caller resumes
+            let result = next.and_then(|next| next.stack.as_deref()).map(|stack| arg(stack, 0));
+
+            nodes.push(CallNode {
+                kind,
+                depth: step.depth + 1,
+                address: address.map(|address| Address::from_word(B256::from(address))),
+                selector,
+                value,
+                gas: match next {
+                    Some(next) if entered => next.gas,
+                    _ => arg(stack, 0).saturating_to(),
+                },
+                success: !entered && result.is_some_and(|result| result != U256::ZERO),
+                first_step: if entered { index + 1 } else { index },
+                last_step: if entered { steps.len() - 1 } else { index },
+                parent: Some(parent),
+                children: vec![],
+            });
+            let node_index = nodes.len() - 1;
+            nodes[parent].children.push(node_index);
+            if entered {
+                open.push(node_index);
+            }
+        }
+
+        Self { nodes }
+    }
+
+    /// Innermost frame running the given operation at the given depth
+    pub fn node_at(&self, step: usize, depth: u64) -> usize {
+        let mut index = 0;
+        while let Some(child) = self.nodes.get(index).and_then(|node| {
+            node.children.iter().copied().find(|child| {
+                let child = &self.nodes[*child];
+                child.depth <= depth && child.contains(step)
+            })
+        }) {
+            index = child;
+        }
+        index
+    }
+
+    /// Step after the innermost failed frame around the given node, where everything the node
+    /// wrote is undone
+    pub fn reverted_at(&self, mut index: usize) -> Option<usize> {
+        loop {
+            let node = self.nodes.get(index)?;
+            if !node.success {
+                return Some(node.last_step + 1);
+            }
+            index = node.parent?;
+        }
+    }
+
+    /// Contract whose storage the frame of the given node uses, DELEGATECALL and CALLCODE run
+    /// on the storage of their caller
+    pub fn storage_address(&self, mut index: usize) -> Option<Address> {
+        loop {
+            let node = self.nodes.get(index)?;
+            match (node.kind, node.parent) {
+                (CallKind::DelegateCall | CallKind::CallCode, Some(parent)) => index = parent,
+                _ => return node.address,
+            }
+        }
+    }
+
+    /// Nodes that are visible when the given nodes are collapsed, in depth-first order
+    pub fn visible(&self, collapsed: &[usize]) -> Vec<usize> {
+        let mut visible = vec![];
+        let mut pending = vec![0];
+        while let Some(index) = pending.pop() {
+            let Some(node) = self.nodes.get(index) else {
+                continue;
+            };
+            visible.push(index);
+            if !collapsed.contains(&index) {
+                pending.extend(node.children.iter().rev());
+            }
+        }
+        visible
+    }
+}
@@ -1,20 +1,157 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use alloy::{
+    primitives::{Address, Bytes, U256},
+    rpc::types::eth::BlockNumberOrTag,
+};
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    breakpoint::Breakpoint,
+    replay::{Hardfork, PLAYGROUND_CALLER},
+    source::TraceBackend,
+    watchpoint::Watchpoint,
+};
 
 static ANVIL_DEFAULT_RPC: &str = "http://127.0.0.1:8545";
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
     /// Transaction hash
-    #[arg(short, long, num_args=1..=2)]
+    #[arg(short, long, num_args=1..=2, required_unless_present_any = ["trace_file", "block"])]
     pub transaction: Vec<String>,
+    /// Pick the transaction from a list of the transactions of a block, a number or `latest`
+    #[arg(long, conflicts_with_all = ["transaction", "trace_file"])]
+    pub block: Option<BlockNumberOrTag>,
+    /// Trace every transaction of the picked block with a single debug_traceBlockByNumber
+    /// call, which needs the debug tracer
+    #[arg(long, requires = "block", conflicts_with = "no_memory")]
+    pub trace_block: bool,
+    /// Saved debug_traceTransaction response to load instead of calling the RPC, `-` for stdin
+    #[arg(long, num_args=1..=2, conflicts_with = "transaction")]
+    pub trace_file: Vec<PathBuf>,
+    /// Transaction JSON matching each trace file, `-` for stdin
+    #[arg(long, num_args=1..=2, requires = "trace_file")]
+    pub transaction_file: Vec<PathBuf>,
     /// Frames per second
-    #[arg(short, long, default_value_t = 4.0)]
+    #[arg(short, long, default_value_t = 4.0, global = true)]
     pub fps: f64,
     /// Operations to process with each frame
-    #[arg(short, long, default_value_t = 1)]
+    #[arg(short, long, default_value_t = 1, global = true)]
     pub iteration: u64,
-    /// The JSON-RPC endpoint URL
-    #[arg(short, long, default_value_t = ANVIL_DEFAULT_RPC.to_string())]
+    /// The JSON-RPC endpoint: an http(s):// or ws(s):// URL, or an IPC socket path
+    #[arg(short, long, default_value_t = ANVIL_DEFAULT_RPC.to_string(), global = true)]
     pub rpc: String,
+    /// Extra header sent with every RPC request, as `Name: value`
+    #[arg(long = "header", global = true)]
+    pub headers: Vec<String>,
+    /// File with the hex encoded secret used to sign a JWT for every RPC request
+    #[arg(long, global = true)]
+    pub jwt_secret: Option<PathBuf>,
+    /// Timeout of a single RPC request in seconds, also passed to the node's tracer
+    #[arg(long, global = true)]
+    pub rpc_timeout: Option<u64>,
+    /// Number of times a failed RPC request is retried
+    #[arg(long, default_value_t = 0, global = true)]
+    pub rpc_retries: u32,
+    /// Delay before the first retry in milliseconds, doubled after every attempt
+    #[arg(long, default_value_t = 1000, global = true)]
+    pub rpc_backoff: u64,
+    /// How traces of mined transactions are produced
+    #[arg(long, value_enum, default_value_t = TraceBackend::Debug)]
+    pub tracer: TraceBackend,
+    /// Hardfork the replay tracer and `run` execute with, instead of the one active at the block
+    #[arg(long, value_enum, global = true)]
+    pub hardfork: Option<Hardfork>,
+    /// Neither read traces from nor write traces to the on-disk cache
+    #[arg(long, conflicts_with = "refresh")]
+    pub no_cache: bool,
+    /// Fetch the traces again and overwrite the cached ones
+    #[arg(long)]
+    pub refresh: bool,
+    /// Track the status of every byte of memory and colour the raw view byte by byte
+    #[arg(long, global = true)]
+    pub byte_statuses: bool,
+    /// Fetch traces without memory snapshots and rebuild memory locally from the stack
+    #[arg(long, global = true)]
+    pub no_memory: bool,
+    /// Pause playback on a condition: `op <opcode>`, `pc <pc>`, `address <address>`,
+    /// `depth <depth>`, `gas below <gas>` or `memory above <bytes>`
+    #[arg(long = "break", global = true)]
+    pub breakpoints: Vec<Breakpoint>,
+    /// Pause playback whenever memory words are read or written, as `3`, `2-5` or `fmp` for
+    /// the free memory pointer
+    #[arg(long = "watch", global = true)]
+    pub watchpoints: Vec<Watchpoint>,
+    /// ABI JSON or Foundry/Hardhat artifact to decode calldata with
+    #[arg(long, global = true)]
+    pub abi: Vec<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Manage the on-disk trace cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+    /// Profile a call that was never mined using debug_traceCall
+    Call(CallArgs),
+    /// Execute raw runtime bytecode in an embedded EVM without a node
+    Run(RunArgs),
+}
+
+#[derive(Args)]
+pub struct CallArgs {
+    /// Address of the called contract
+    #[arg(long)]
+    pub to: Address,
+    /// Calldata of the call
+    #[arg(long, default_value_t = Bytes::new())]
+    pub data: Bytes,
+    /// Sender of the call
+    #[arg(long)]
+    pub from: Option<Address>,
+    /// Wei sent along with the call
+    #[arg(long, default_value_t = U256::ZERO)]
+    pub value: U256,
+    /// Block to execute the call on top of
+    #[arg(long, default_value_t = BlockNumberOrTag::Latest)]
+    pub block: BlockNumberOrTag,
+    /// JSON file with state overrides keyed by account address
+    #[arg(long)]
+    pub state_overrides: Option<PathBuf>,
+    /// JSON file with block overrides
+    #[arg(long)]
+    pub block_overrides: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// List the cached traces
+    Ls,
+    /// Remove every cached trace
+    Clear,
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// Hex encoded runtime bytecode, or a file containing it
+    #[arg(long)]
+    pub code: String,
+    /// Calldata of the call
+    #[arg(long, default_value_t = Bytes::new())]
+    pub calldata: Bytes,
+    /// Wei sent along with the call
+    #[arg(long, default_value_t = U256::ZERO)]
+    pub value: U256,
+    /// Sender of the call
+    #[arg(long, default_value_t = PLAYGROUND_CALLER)]
+    pub from: Address,
+    /// JSON file with the accounts to seed the state with, in the genesis `alloc` format
+    #[arg(long)]
+    pub state: Option<PathBuf>,
 }
@@ -0,0 +1,66 @@
+use alloy::rpc::types::trace::geth::StructLog;
+
+use crate::access::{self, MemoryAccess};
+
+/// Gas a frame has paid for memory of the given number of words, saturating at `u64::MAX`
+pub fn memory_cost(words: usize) -> u64 {
+    let words = words as u128;
+    let cost = words.saturating_mul(words) / 512 + 3 * words;
+    cost.try_into().unwrap_or(u64::MAX)
+}
+
+/// Part of the gas cost of an operation paid for expanding memory, the rest is its base cost
+pub fn expansion_cost(step: &StructLog) -> u64 {
+    // A failed operation expands nothing, and running out of gas is often caused by offsets
+    // far beyond anything that could be paid for
+    if step.error.is_some() {
+        return 0;
+    }
+    let stack = step.stack.as_deref().unwrap_or_default();
+    // Memory snapshots are taken before the operation runs
+    let words = step.memory.as_ref().map_or(0, Vec::len);
+    let access = MemoryAccess::from_operation(&step.op, stack, words * 32);
+    let expanded = access
+        .reads
+        .iter()
+        .chain(&access.writes)
+        .map(|range| access::slots(range).end)
+        .fold(words, usize::max);
+    // The expansion is part of what the operation paid
+    (memory_cost(expanded) - memory_cost(words)).min(step.gas_cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::test_utils::{self, with_gas_cost, with_memory};
+
+    /// A step with the given stack, top of the stack last, memory size in words and gas cost
+    fn step(op: &str, stack: &[u64], memory_words: usize, gas_cost: u64) -> Value {
+        with_gas_cost(with_memory(test_utils::step(1, op, stack), memory_words), gas_cost)
+    }
+
+    fn expansion(step: Value) -> u64 {
+        expansion_cost(&serde_json::from_value(step).unwrap())
+    }
+
+    #[test]
+    fn expansion_is_split_from_the_base_cost() {
+        assert_eq!(expansion(step("MSTORE", &[1, 0], 0, 6)), 3);
+        // Writing the third word expands memory by two words at once
+        assert_eq!(expansion(step("MSTORE", &[1, 0x40], 1, 9)), 6);
+        assert_eq!(expansion(step("MLOAD", &[0], 3, 3)), 0);
+        assert_eq!(memory_cost(1024), 5120);
+    }
+
+    #[test]
+    fn out_of_gas_offsets_cost_nothing_to_expand() {
+        let mut out_of_gas = step("MSTORE", &[1, u64::MAX], 0, 100000);
+        assert_eq!(expansion(out_of_gas.clone()), 100000);
+        out_of_gas["error"] = json!("out of gas");
+        assert_eq!(expansion(out_of_gas), 0);
+        assert_eq!(memory_cost(usize::MAX), u64::MAX);
+    }
+}
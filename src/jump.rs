@@ -0,0 +1,111 @@
+use std::str::FromStr;
+
+use alloy::primitives::U256;
+use color_eyre::eyre::{self, eyre};
+
+use crate::{
+    access::{self, MemoryAccess},
+    number::parse_number,
+    state::TransactionState,
+};
+
+/// Where the jump-to command sends playback
+#[derive(Debug, Clone, PartialEq)]
+pub enum Jump {
+    /// Operation number
+    Step(usize),
+    /// Next operation at this program counter
+    Pc(u64),
+    /// Next operation with this opcode
+    Opcode(String),
+    /// Next operation that reads this word of memory
+    Read(usize),
+    /// Next operation that writes this word of memory
+    Write(usize),
+}
+
+impl FromStr for Jump {
+    type Err = eyre::Error;
+
+    /// Accepts `<step>`, `pc <pc>`, `<opcode>`, `read <word>` and `write <word>`. Filler words
+    /// are ignored, so `next write to word 4` works too
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.to_lowercase();
+        let words: Vec<&str> = text
+            .split_whitespace()
+            .filter(|word| !matches!(*word, "next" | "to" | "word" | "step"))
+            .collect();
+
+        match words.as_slice() {
+            [number] if number.starts_with(|c: char| c.is_ascii_digit()) => {
+                Ok(Jump::Step(parse_number(number)? as usize))
+            }
+            ["pc", pc] => Ok(Jump::Pc(parse_number(pc)?)),
+            ["read", word] => Ok(Jump::Read(parse_number(word)? as usize)),
+            ["write", word] => Ok(Jump::Write(parse_number(word)? as usize)),
+            [opcode] if opcode.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                Ok(Jump::Opcode(opcode.to_uppercase()))
+            }
+            _ => Err(eyre!("Unknown jump {:?}", text)),
+        }
+    }
+}
+
+impl Jump {
+    /// Operation the jump lands on, searching forward from the operation after the current one
+    pub fn target(&self, transaction_state: &TransactionState) -> Result<usize, eyre::Error> {
+        let steps = &transaction_state.raw_data;
+        let from = transaction_state.next_operation as usize;
+        let found = match self {
+            Jump::Step(step) if *step < steps.len() => return Ok(*step),
+            Jump::Step(step) => {
+                let last = steps.len().saturating_sub(1);
+                return Err(eyre!("Step {} is past the last step {}", step, last));
+            }
+            Jump::Pc(pc) => steps.iter().skip(from).position(|step| step.pc == *pc),
+            Jump::Opcode(opcode) => steps.iter().skip(from).position(|step| step.op == *opcode),
+            Jump::Read(word) | Jump::Write(word) => {
+                steps.iter().skip(from).position(|step| {
+                    let stack: &[U256] = step.stack.as_deref().unwrap_or_default();
+                    let memory_size = step.memory.as_ref().map_or(0, Vec::len) * 32;
+                    let access = MemoryAccess::from_operation(&step.op, stack, memory_size);
+                    let ranges = match self {
+                        Jump::Read(_) => access.reads,
+                        _ => access.writes,
+                    };
+                    ranges.iter().any(|range| access::slots(range).contains(word))
+                })
+            }
+        };
+        found
+            .map(|offset| from + offset)
+            .ok_or_else(|| eyre!("No match after step {}", from.saturating_sub(1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{step, struct_logs, with_memory};
+
+    /// A transaction of a single frame whose first step was just processed
+    fn transaction_state(steps: &[(&str, &[u64], usize)]) -> TransactionState {
+        let steps = steps.iter().map(|(op, stack, words)| with_memory(step(1, op, stack), *words));
+        let raw_data = struct_logs(steps.collect());
+        TransactionState { raw_data, next_operation: 1, ..Default::default() }
+    }
+
+    #[test]
+    fn jump_to_the_next_write_of_a_word() {
+        let state = transaction_state(&[
+            ("MSTORE", &[1, 0x20], 0),
+            ("MLOAD", &[0x20], 2),
+            ("MSTORE", &[2, 0x30], 2),
+            ("STOP", &[], 3),
+        ]);
+        let jump: Jump = "next write to word 2".parse().unwrap();
+        assert_eq!(jump.target(&state).unwrap(), 2);
+        assert_eq!("read 1".parse::<Jump>().unwrap().target(&state).unwrap(), 1);
+        assert!("write 5".parse::<Jump>().unwrap().target(&state).is_err());
+    }
+}
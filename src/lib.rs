@@ -1,7 +1,29 @@
+mod access;
 mod app;
+mod block;
+mod breakpoint;
+mod cache;
+mod calldata;
+mod calltree;
+mod gas;
+mod jump;
 mod log;
+mod memory;
+mod number;
+mod opcodes;
+mod parity;
+mod picker;
 mod provider;
 mod render;
+mod replay;
+mod returndata;
+mod source;
+mod stack;
 mod state;
+mod storage;
+#[cfg(test)]
+mod test_utils;
+mod timeline;
 mod tui;
 mod types;
+mod watchpoint;
@@ -1,35 +1,210 @@
+mod access;
 mod app;
+mod block;
+mod breakpoint;
+mod cache;
+mod calldata;
+mod calltree;
 mod cli;
+mod gas;
+mod jump;
 mod log;
+mod memory;
+mod number;
+mod opcodes;
+mod parity;
+mod picker;
 mod provider;
 mod render;
+mod replay;
+mod returndata;
+mod source;
+mod stack;
 mod state;
+mod storage;
+#[cfg(test)]
+mod test_utils;
+mod timeline;
 mod tui;
+mod watchpoint;
 
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
-use alloy::primitives::TxHash;
+use alloy::{primitives::TxHash, rpc::types::eth::BlockNumberOrTag};
 use app::App;
+use cache::CacheMode;
+use calldata::Abi;
 use clap::Parser;
-use cli::Cli;
+use cli::{CacheCommand, CallArgs, Cli, Command, RunArgs};
 use color_eyre::{eyre, Result};
 use log::initialize_logging;
+use provider::{RpcConfig, RpcProvider};
+use replay::Hardfork;
+use source::{BytecodeRequest, CallRequest, TraceBackend, TraceSource};
 use state::AppState;
 
 #[tokio::main]
 async fn main() -> Result<(), eyre::Error> {
     initialize_logging()?;
-    let cli = Cli::parse();
-    let transactions: Vec<TxHash> = cli
-        .transaction
-        .iter()
-        .map(|transaction| TxHash::from_str(transaction.as_str()).unwrap())
-        .collect();
+    let mut cli = Cli::parse();
+
+    let sources = match cli.command.take() {
+        Some(Command::Cache { action }) => return run_cache_command(action),
+        Some(Command::Call(call)) => {
+            vec![call_source(call, rpc_config(&cli)?, cli.no_memory)]
+        }
+        Some(Command::Run(run)) => vec![bytecode_source(run, cli.hardfork)],
+        None => match cli.block {
+            Some(block) => match block_source(&cli, block).await? {
+                Some(source) => vec![source],
+                None => return Ok(()),
+            },
+            None => transaction_sources(&cli)?,
+        },
+    };
     let fps = cli.fps;
     let iteration = cli.iteration;
-    let rpc = cli.rpc;
-    let mut app_state = AppState::default();
-    app_state.init(&rpc, transactions).await?;
+    let mut app_state = AppState {
+        byte_statuses: cli.byte_statuses,
+        breakpoints: cli.breakpoints,
+        watchpoints: cli.watchpoints,
+        abi: Abi::load(&cli.abi)?,
+        ..Default::default()
+    };
+    app_state.init(sources).await?;
     App::default().run(&mut app_state, fps, iteration).await?;
     Ok(())
 }
+
+fn rpc_config(cli: &Cli) -> Result<RpcConfig, eyre::Error> {
+    Ok(RpcConfig {
+        headers: cli
+            .headers
+            .iter()
+            .map(|header| RpcConfig::parse_header(header))
+            .collect::<Result<_, _>>()?,
+        jwt_secret: cli.jwt_secret.clone(),
+        timeout: cli.rpc_timeout.map(Duration::from_secs),
+        retries: cli.rpc_retries,
+        backoff: Duration::from_millis(cli.rpc_backoff),
+        ..RpcConfig::new(&cli.rpc)
+    })
+}
+
+fn cache_mode(cli: &Cli) -> CacheMode {
+    if cli.no_cache {
+        CacheMode::Disabled
+    } else if cli.refresh {
+        CacheMode::Refresh
+    } else {
+        CacheMode::Enabled
+    }
+}
+
+fn transaction_sources(cli: &Cli) -> Result<Vec<TraceSource>, eyre::Error> {
+    let cache = cache_mode(cli);
+    let sources: Vec<TraceSource> = if cli.trace_file.is_empty() {
+        let rpc = rpc_config(cli)?;
+        cli.transaction
+            .iter()
+            .map(|transaction| {
+                Ok(TraceSource::Rpc {
+                    rpc: rpc.clone(),
+                    transaction: TxHash::from_str(transaction.as_str())?,
+                    cache,
+                    backend: cli.tracer,
+                    reconstruct_memory: cli.no_memory,
+                    hardfork: cli.hardfork,
+                })
+            })
+            .collect::<Result<_, eyre::Error>>()?
+    } else {
+        cli.trace_file
+            .iter()
+            .enumerate()
+            .map(|(index, trace)| TraceSource::File {
+                trace: trace.clone(),
+                transaction: cli.transaction_file.get(index).cloned(),
+            })
+            .collect()
+    };
+    Ok(sources)
+}
+
+/// Lets the user pick a transaction of the block, `None` if they quit without picking one
+async fn block_source(
+    cli: &Cli,
+    block: BlockNumberOrTag,
+) -> Result<Option<TraceSource>, eyre::Error> {
+    // The whole block is traced with debug_traceBlockByNumber, no other backend can do that
+    if cli.trace_block && cli.tracer != TraceBackend::Debug {
+        return Err(eyre::eyre!("--trace-block only works with --tracer debug"));
+    }
+    let rpc = rpc_config(cli)?;
+    let provider = RpcProvider::init(&rpc).await?;
+    let block = block::fetch_block(&rpc, &provider, block).await?;
+    let Some(index) = picker::pick_transaction(&block).await? else {
+        return Ok(None);
+    };
+
+    if cli.trace_block {
+        let mut traces = block::trace_block(&rpc, &provider, &block, cache_mode(cli)).await?;
+        return Ok(Some(TraceSource::Loaded(Box::new(traces.swap_remove(index)))));
+    }
+
+    Ok(Some(TraceSource::Rpc {
+        rpc,
+        transaction: block.transactions[index].transaction.hash,
+        cache: cache_mode(cli),
+        backend: cli.tracer,
+        reconstruct_memory: cli.no_memory,
+        hardfork: cli.hardfork,
+    }))
+}
+
+fn call_source(call: CallArgs, rpc: RpcConfig, reconstruct_memory: bool) -> TraceSource {
+    TraceSource::Call(CallRequest {
+        rpc,
+        from: call.from,
+        to: call.to,
+        data: call.data,
+        value: call.value,
+        block: call.block,
+        state_overrides: call.state_overrides,
+        block_overrides: call.block_overrides,
+        reconstruct_memory,
+    })
+}
+
+fn bytecode_source(run: RunArgs, hardfork: Option<Hardfork>) -> TraceSource {
+    TraceSource::Bytecode(BytecodeRequest {
+        code: run.code,
+        calldata: run.calldata,
+        value: run.value,
+        caller: run.from,
+        state: run.state,
+        hardfork,
+    })
+}
+
+fn run_cache_command(action: CacheCommand) -> Result<(), eyre::Error> {
+    match action {
+        CacheCommand::Ls => {
+            let entries = cache::list()?;
+            if entries.is_empty() {
+                println!("The cache at {} is empty", cache::cache_directory().display());
+            }
+            for entry in entries {
+                println!(
+                    "{:>8}  {}  {:<16}  {:>10} bytes",
+                    entry.chain_id, entry.transaction, entry.variant, entry.size
+                );
+            }
+        }
+        CacheCommand::Clear => {
+            let removed = cache::clear()?;
+            println!("Removed {} cached traces", removed);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+
+use alloy::{
+    primitives::{Address, Bytes, B256, U256},
+    providers::{Provider, RootProvider},
+    rpc::types::{
+        eth::{BlockId, Transaction},
+        trace::geth::StructLog,
+    },
+    transports::BoxTransport,
+};
+use color_eyre::eyre::{self, eyre};
+
+use crate::{
+    access::{arg, arg_usize, call_args, MemoryAccess},
+    provider::RpcConfig,
+};
+
+/// Splits memory into the 32-byte hex words used by struct logs
+pub fn to_words(memory: &[u8]) -> Vec<String> {
+    memory
+        .chunks(32)
+        .map(|word| word.iter().map(|byte| format!("{:02x}", byte)).collect())
+        .collect()
+}
+
+/// Grows memory in 32-byte words so that it covers the given range, zero-sized ranges never
+/// expand memory. Ranges past the address space cannot be paid for, so they do not either
+fn expand(memory: &mut Vec<u8>, offset: usize, size: usize) {
+    if size == 0 {
+        return;
+    }
+    let Some(end) = offset.checked_add(size).and_then(|end| end.checked_next_multiple_of(32))
+    else {
+        return;
+    };
+    if memory.len() < end {
+        memory.resize(end, 0);
+    }
+}
+
+fn write(memory: &mut Vec<u8>, offset: usize, data: &[u8]) {
+    expand(memory, offset, data.len());
+    if let Some(target) = offset
+        .checked_add(data.len())
+        .and_then(|end| memory.get_mut(offset..end))
+    {
+        target.copy_from_slice(data);
+    }
+}
+
+/// Reads a range of a buffer, padding the part past its end with zeroes like the COPY opcodes do
+fn read_padded(source: &[u8], offset: U256, size: usize) -> Vec<u8> {
+    let mut data = vec![0; size];
+    let offset = offset.saturating_to::<usize>();
+    if offset < source.len() {
+        let available = (source.len() - offset).min(size);
+        data[..available].copy_from_slice(&source[offset..offset + available]);
+    }
+    data
+}
+
+/// What the caller does with the result of a sub frame
+enum PendingCall {
+    /// Copy the returned data into this range of the caller's memory
+    Call { offset: usize, size: usize },
+    /// Contract creation, the returned data is the deployed code
+    Create,
+}
+
+#[derive(Default)]
+struct Frame {
+    memory: Vec<u8>,
+    calldata: Bytes,
+    /// Address whose code the frame executes, `None` for contract creations
+    code_address: Option<Address>,
+    /// Init code of contract creations
+    init_code: Option<Bytes>,
+    /// Data returned by the last sub call
+    return_data: Bytes,
+    pending: Option<PendingCall>,
+}
+
+/// Rebuilds the memory snapshots of struct logs fetched without `enableMemory` by simulating
+/// every memory-touching opcode from its stack arguments
+pub struct MemoryReconstructor<'a> {
+    rpc: &'a RpcConfig,
+    provider: &'a RootProvider<BoxTransport>,
+    /// Block whose state the code of the called contracts is read from
+    block: BlockId,
+    codes: HashMap<Address, Bytes>,
+}
+
+impl<'a> MemoryReconstructor<'a> {
+    pub fn new(
+        rpc: &'a RpcConfig,
+        provider: &'a RootProvider<BoxTransport>,
+        block: BlockId,
+    ) -> Self {
+        Self { rpc, provider, block, codes: HashMap::new() }
+    }
+
+    /// Uses the given code instead of the one on chain, e.g. for state overrides of a call
+    pub fn override_code(mut self, codes: impl IntoIterator<Item = (Address, Bytes)>) -> Self {
+        self.codes.extend(codes);
+        self
+    }
+
+    async fn code(&mut self, address: Address) -> Result<Bytes, eyre::Error> {
+        if let Some(code) = self.codes.get(&address) {
+            return Ok(code.clone());
+        }
+        let code = self
+            .rpc
+            .request("eth_getCode", || self.provider.get_code_at(address, self.block))
+            .await?;
+        self.codes.insert(address, code.clone());
+        Ok(code)
+    }
+
+    async fn frame_code(&mut self, frame: &Frame) -> Result<Bytes, eyre::Error> {
+        match (&frame.init_code, frame.code_address) {
+            (Some(init_code), _) => Ok(init_code.clone()),
+            (None, Some(address)) => self.code(address).await,
+            (None, None) => Ok(Bytes::new()),
+        }
+    }
+
+    pub async fn reconstruct(
+        &mut self,
+        struct_logs: &mut [StructLog],
+        transaction: &Transaction,
+    ) -> Result<(), eyre::Error> {
+        let mut frames = initial_frames(transaction);
+        for index in 0..struct_logs.len() {
+            let code = match frames.last() {
+                Some(frame) => self.copied_code(frame, &struct_logs[index]).await?,
+                None => None,
+            };
+            step(&mut frames, struct_logs, index, code.as_deref())?;
+        }
+        Ok(())
+    }
+
+    /// Code the CODECOPY or EXTCODECOPY of the given step copies from
+    async fn copied_code(
+        &mut self,
+        frame: &Frame,
+        step: &StructLog,
+    ) -> Result<Option<Bytes>, eyre::Error> {
+        if step.error.is_some() {
+            return Ok(None);
+        }
+        let stack = step.stack.as_deref().unwrap_or_default();
+        match step.op.as_str() {
+            "CODECOPY" => Ok(Some(self.frame_code(frame).await?)),
+            "EXTCODECOPY" => {
+                Ok(Some(self.code(Address::from_word(B256::from(arg(stack, 0)))).await?))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Frame of the transaction itself
+fn initial_frames(transaction: &Transaction) -> Vec<Frame> {
+    vec![match transaction.to {
+        Some(to) => Frame {
+            calldata: transaction.input.clone(),
+            code_address: Some(to),
+            ..Default::default()
+        },
+        None => Frame { init_code: Some(transaction.input.clone()), ..Default::default() },
+    }]
+}
+
+/// Records the memory of the frame running the given step and applies the step to it, entering
+/// and leaving frames as the depth changes. `code` is what a CODECOPY or EXTCODECOPY copies from
+fn step(
+    frames: &mut Vec<Frame>,
+    struct_logs: &mut [StructLog],
+    index: usize,
+    code: Option<&[u8]>,
+) -> Result<(), eyre::Error> {
+    let depth = struct_logs[index].depth;
+    let next_depth = struct_logs.get(index + 1).map(|next| next.depth);
+    let op = struct_logs[index].op.clone();
+    let stack = struct_logs[index].stack.clone().unwrap_or_default();
+    // A failed operation has no effect, and an out-of-gas one may name ranges far beyond
+    // anything that fits in memory
+    let failed = struct_logs[index].error.is_some();
+
+    let Some(mut frame) = frames.pop() else {
+        return Err(eyre!("Step {} runs outside of any call frame", index));
+    };
+    struct_logs[index].memory = Some(to_words(&frame.memory));
+    if !failed {
+        apply(&mut frame, &op, &stack, code.unwrap_or_default());
+    }
+
+    match next_depth {
+        Some(next_depth) if next_depth > depth => {
+            let callee = enter(&mut frame, &op, &stack);
+            frames.push(frame);
+            frames.push(callee);
+        }
+        Some(next_depth) if next_depth < depth => {
+            let returned = match op.as_str() {
+                "RETURN" | "REVERT" if !failed => {
+                    Bytes::from(read_padded(&frame.memory, arg(&stack, 0), arg_usize(&stack, 1)))
+                }
+                _ => Bytes::new(),
+            };
+            let Some(caller) = frames.last_mut() else {
+                return Err(eyre!("Step {} returns from the outermost frame", index));
+            };
+            // A failing operation reverts the frame just like REVERT does
+            let reverted = op == "REVERT" || failed;
+            finish_call(caller, returned, reverted);
+        }
+        Some(_) if is_call(&op) && !failed => {
+            // Calls to precompiles and accounts without code have no steps of their own,
+            // so their output can only be taken from the return data of the next step
+            let _ = enter(&mut frame, &op, &stack);
+            let returned = struct_logs[index + 1].return_data.clone().unwrap_or_default();
+            finish_call(&mut frame, returned, false);
+            frames.push(frame);
+        }
+        _ => frames.push(frame),
+    }
+    Ok(())
+}
+
+/// Applies the memory effects an opcode has on its own frame, the return buffer of calls is
+/// only expanded here and filled once the callee returns
+fn apply(frame: &mut Frame, op: &str, stack: &[U256], code: &[u8]) {
+    let access = MemoryAccess::from_operation(op, stack, frame.memory.len());
+    for range in access.reads.iter().chain(&access.writes) {
+        expand(&mut frame.memory, range.start, range.len());
+    }
+
+    match op {
+        "MSTORE" => {
+            write(&mut frame.memory, arg_usize(stack, 0), &arg(stack, 1).to_be_bytes::<32>())
+        }
+        "MSTORE8" => write(&mut frame.memory, arg_usize(stack, 0), &[arg(stack, 1).byte(0)]),
+        "CALLDATACOPY" => {
+            let data = read_padded(&frame.calldata, arg(stack, 1), arg_usize(stack, 2));
+            write(&mut frame.memory, arg_usize(stack, 0), &data);
+        }
+        "CODECOPY" => {
+            let data = read_padded(code, arg(stack, 1), arg_usize(stack, 2));
+            write(&mut frame.memory, arg_usize(stack, 0), &data);
+        }
+        "EXTCODECOPY" => {
+            let data = read_padded(code, arg(stack, 2), arg_usize(stack, 3));
+            write(&mut frame.memory, arg_usize(stack, 1), &data);
+        }
+        "RETURNDATACOPY" => {
+            let data = read_padded(&frame.return_data, arg(stack, 1), arg_usize(stack, 2));
+            write(&mut frame.memory, arg_usize(stack, 0), &data);
+        }
+        "MCOPY" => {
+            let data = read_padded(&frame.memory, arg(stack, 1), arg_usize(stack, 2));
+            write(&mut frame.memory, arg_usize(stack, 0), &data);
+        }
+        // Everything else only expands memory, which the access ranges already did
+        _ => {}
+    }
+}
+
+/// Creates the frame a call or create opcode enters and records what the caller does with
+/// its result
+fn enter(caller: &mut Frame, op: &str, stack: &[U256]) -> Frame {
+    match op {
+        "CREATE" | "CREATE2" => {
+            caller.pending = Some(PendingCall::Create);
+            Frame {
+                init_code: Some(Bytes::from(read_padded(
+                    &caller.memory,
+                    arg(stack, 1),
+                    arg_usize(stack, 2),
+                ))),
+                ..Default::default()
+            }
+        }
+        _ => {
+            let first = call_args(op);
+            caller.pending = Some(PendingCall::Call {
+                offset: arg_usize(stack, first + 2),
+                size: arg_usize(stack, first + 3),
+            });
+            Frame {
+                calldata: Bytes::from(read_padded(
+                    &caller.memory,
+                    arg(stack, first),
+                    arg_usize(stack, first + 1),
+                )),
+                code_address: Some(Address::from_word(B256::from(arg(stack, 1)))),
+                ..Default::default()
+            }
+        }
+    }
+}
+
+fn is_call(op: &str) -> bool {
+    matches!(op, "CALL" | "CALLCODE" | "DELEGATECALL" | "STATICCALL")
+}
+
+/// Hands the data returned by a sub frame to its caller
+fn finish_call(caller: &mut Frame, returned: Bytes, reverted: bool) {
+    match caller.pending.take() {
+        Some(PendingCall::Call { offset, size }) => {
+            let copied = size.min(returned.len());
+            write(&mut caller.memory, offset, &returned[..copied]);
+            caller.return_data = returned;
+        }
+        // A successful creation leaves the return data buffer empty
+        Some(PendingCall::Create) if !reverted => caller.return_data = Bytes::new(),
+        _ => caller.return_data = returned,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::test_utils::{step as log, struct_logs};
+
+    /// Rebuilds the memory of a trace that copies no code, returning the words of every step
+    fn reconstruct(logs: Vec<Value>, input: &[u8]) -> Vec<Vec<String>> {
+        let mut struct_logs = struct_logs(logs);
+        let transaction = Transaction {
+            to: Some(Address::repeat_byte(0xaa)),
+            input: Bytes::copy_from_slice(input),
+            ..Default::default()
+        };
+        let mut frames = initial_frames(&transaction);
+        for index in 0..struct_logs.len() {
+            step(&mut frames, &mut struct_logs, index, None).unwrap();
+        }
+        struct_logs.into_iter().map(|log| log.memory.unwrap()).collect()
+    }
+
+    fn stack(values: &[u64]) -> Vec<U256> {
+        values.iter().map(|value| U256::from(*value)).collect()
+    }
+
+    #[test]
+    fn mstore8_writes_the_lowest_byte_of_the_value() {
+        let memories =
+            reconstruct(vec![log(1, "MSTORE8", &[0x1234, 1]), log(1, "STOP", &[])], &[]);
+        assert_eq!(memories[0], Vec::<String>::new());
+        assert_eq!(memories[1], vec![format!("{:0<64}", "0034")]);
+    }
+
+    #[test]
+    fn copies_pad_past_the_end_of_their_source() {
+        let mut frame = Frame {
+            memory: vec![0xff; 64],
+            calldata: Bytes::from_static(&[0x11, 0x22]),
+            ..Default::default()
+        };
+        apply(&mut frame, "CALLDATACOPY", &stack(&[3, 1, 0]), &[]);
+        assert_eq!(frame.memory[..4], [0x22, 0, 0, 0xff]);
+
+        apply(&mut frame, "CODECOPY", &stack(&[3, 1, 32]), &[0x60, 0x01]);
+        assert_eq!(frame.memory[32..36], [0x01, 0, 0, 0xff]);
+
+        // Copies past the end of memory read zeroes from the part they expand
+        apply(&mut frame, "MCOPY", &stack(&[4, 62, 0]), &[]);
+        assert_eq!(frame.memory[..5], [0xff, 0xff, 0, 0, 0xff]);
+        assert_eq!(frame.memory.len(), 96);
+    }
+
+    #[test]
+    fn call_output_is_written_to_the_caller_when_the_callee_returns() {
+        let memories = reconstruct(
+            vec![
+                log(1, "MSTORE", &[0xaa, 0]),
+                log(1, "CALL", &[2, 0x40, 0, 0, 0, 0xbb, 1000]),
+                log(2, "MSTORE", &[0xbeef, 0]),
+                log(2, "RETURN", &[2, 30]),
+                log(1, "STOP", &[]),
+            ],
+            &[],
+        );
+        let stored = format!("{:064x}", 0xaa);
+        assert_eq!(memories[1], vec![stored.clone()]);
+        // The callee starts with memory of its own
+        assert_eq!(memories[2], Vec::<String>::new());
+        assert_eq!(memories[3], vec![format!("{:064x}", 0xbeef)]);
+        // The caller resumes with its memory and the output in the range it asked for
+        assert_eq!(memories[4], vec![stored, "00".repeat(32), format!("{:0<64}", "beef")]);
+    }
+
+    #[test]
+    fn failed_steps_leave_memory_untouched() {
+        let mut out_of_gas = log(2, "MSTORE", &[1, u64::MAX]);
+        out_of_gas["error"] = json!("out of gas");
+        let memories = reconstruct(
+            vec![log(1, "CALL", &[0, 0, 0, 0, 0, 0xbb, 1000]), out_of_gas, log(1, "STOP", &[])],
+            &[],
+        );
+        assert_eq!(memories[2], Vec::<String>::new());
+    }
+}
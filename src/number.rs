@@ -0,0 +1,10 @@
+use color_eyre::eyre::{self, eyre};
+
+/// Parses a decimal or 0x-prefixed hex number
+pub fn parse_number(text: &str) -> Result<u64, eyre::Error> {
+    let number = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    number.map_err(|_| eyre!("{} is not a number", text))
+}
@@ -0,0 +1,71 @@
+/// Topic operand names of the LOG opcodes
+static TOPICS: [&str; 4] = ["topic0", "topic1", "topic2", "topic3"];
+
+/// Names of the stack items an opcode consumes, from the top of the stack downwards
+///
+/// opcode-parser only has variants for the opcodes that touch memory and names their
+/// arguments in an unordered map, so it can neither tell which stack item an argument is nor
+/// name the operands of any other opcode. Parity traces also need the operand count of every
+/// opcode to rebuild the stack
+pub fn operands(op: &str) -> Vec<&'static str> {
+    if let Some(n) = op.strip_prefix("DUP").and_then(|n| n.parse::<usize>().ok()) {
+        // Only the duplicated item matters, the ones above it are merely skipped
+        let mut operands = vec![""; n.saturating_sub(1)];
+        operands.push("value");
+        return operands;
+    }
+    if let Some(n) = op.strip_prefix("SWAP").and_then(|n| n.parse::<usize>().ok()) {
+        let mut operands = vec!["a"];
+        operands.extend(vec![""; n.saturating_sub(1)]);
+        operands.push("b");
+        return operands;
+    }
+    if let Some(n) = op.strip_prefix("LOG").and_then(|n| n.parse::<usize>().ok()) {
+        let mut operands = vec!["offset", "size"];
+        operands.extend(TOPICS.iter().take(n));
+        return operands;
+    }
+
+    let operands: &[&'static str] = match op {
+        "ADD" | "MUL" | "SUB" | "DIV" | "SDIV" | "MOD" | "SMOD" | "LT" | "GT" | "SLT" | "SGT"
+        | "EQ" | "AND" | "OR" | "XOR" => &["a", "b"],
+        "ADDMOD" | "MULMOD" => &["a", "b", "N"],
+        "EXP" => &["a", "exponent"],
+        "SIGNEXTEND" => &["b", "x"],
+        "ISZERO" | "NOT" => &["a"],
+        "BYTE" => &["i", "x"],
+        "SHL" | "SHR" | "SAR" => &["shift", "value"],
+        "SHA3" | "KECCAK256" => &["offset", "size"],
+        "BALANCE" | "EXTCODESIZE" | "EXTCODEHASH" | "SELFDESTRUCT" | "SUICIDE" => &["address"],
+        "CALLDATALOAD" => &["i"],
+        "CALLDATACOPY" | "CODECOPY" | "RETURNDATACOPY" | "MCOPY" => {
+            &["destOffset", "offset", "size"]
+        }
+        "EXTCODECOPY" => &["address", "destOffset", "offset", "size"],
+        "BLOCKHASH" => &["blockNumber"],
+        "BLOBHASH" => &["index"],
+        "POP" => &["y"],
+        "MLOAD" => &["offset"],
+        "MSTORE" | "MSTORE8" => &["offset", "value"],
+        "SLOAD" | "TLOAD" => &["key"],
+        "SSTORE" | "TSTORE" => &["key", "value"],
+        "JUMP" => &["counter"],
+        "JUMPI" => &["counter", "b"],
+        "CREATE" => &["value", "offset", "size"],
+        "CREATE2" => &["value", "offset", "size", "salt"],
+        "CALL" | "CALLCODE" => {
+            &["gas", "address", "value", "argsOffset", "argsSize", "retOffset", "retSize"]
+        }
+        "DELEGATECALL" | "STATICCALL" => {
+            &["gas", "address", "argsOffset", "argsSize", "retOffset", "retSize"]
+        }
+        "RETURN" | "REVERT" => &["offset", "size"],
+        _ => &[],
+    };
+    operands.to_vec()
+}
+
+/// Number of stack items an opcode consumes
+pub fn stack_inputs(op: &str) -> usize {
+    operands(op).len()
+}
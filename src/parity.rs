@@ -0,0 +1,179 @@
+use alloy::{
+    primitives::{TxHash, U256},
+    providers::{Provider, RootProvider},
+    rpc::types::trace::parity::{TraceResults, VmTrace},
+    transports::BoxTransport,
+};
+use color_eyre::eyre::{self, eyre};
+use revm::interpreter::opcode::OpCode;
+use serde_json::json;
+
+use crate::{memory, opcodes, provider::RpcConfig, source::TraceData};
+
+/// Traces a transaction with `trace_replayTransaction` and converts its vmTrace into struct logs
+pub async fn replay_transaction(
+    rpc: &RpcConfig,
+    provider: &RootProvider<BoxTransport>,
+    transaction: TxHash,
+) -> Result<TraceData, eyre::Error> {
+    let transaction_result = rpc
+        .request("eth_getTransactionByHash", || provider.get_transaction_by_hash(transaction))
+        .await?;
+    let results: TraceResults = rpc
+        .request("trace_replayTransaction", || {
+            provider
+                .client()
+                .request("trace_replayTransaction", (transaction, ["vmTrace", "trace"]))
+        })
+        .await?;
+
+    let vm_trace =
+        results.vm_trace.ok_or_else(|| eyre!("No vmTrace was returned for {}", transaction))?;
+    // The first trace belongs to the top level call, its error is the error of the transaction
+    let success = results.trace.first().map_or(true, |trace| trace.error.is_none());
+
+    let mut steps = vec![];
+    convert_frame(&vm_trace, 1, &mut steps);
+
+    Ok(TraceData {
+        transaction: transaction_result,
+        success,
+        struct_logs: serde_json::from_value(serde_json::Value::Array(steps))?,
+    })
+}
+
+/// Replays the stack and memory deltas of a frame and its sub frames into struct log steps.
+///
+/// vmTrace only records what an operation pushed and wrote, so the stack is rebuilt by popping
+/// each opcode's inputs, and memory only grows when it is written to. Expansions caused by reads
+/// alone, e.g. an MLOAD past the end of memory, are not visible until the next write.
+fn convert_frame(trace: &VmTrace, depth: u64, steps: &mut Vec<serde_json::Value>) {
+    let mut stack: Vec<U256> = vec![];
+    let mut memory: Vec<u8> = vec![];
+
+    for instruction in &trace.ops {
+        let op = instruction.op.clone().unwrap_or_else(|| {
+            let opcode = trace.code.get(instruction.pc).copied().unwrap_or_default();
+            OpCode::new(opcode)
+                .map_or(format!("opcode 0x{:x}", opcode), |op| op.as_str().to_string())
+        });
+        let used = instruction.ex.as_ref().map_or(0, |ex| ex.used);
+
+        steps.push(json!({
+            "pc": instruction.pc,
+            "op": op,
+            "gas": used + instruction.cost,
+            "gasCost": instruction.cost,
+            "depth": depth,
+            "stack": stack,
+            "memory": memory::to_words(&memory),
+        }));
+
+        if let Some(sub) = &instruction.sub {
+            convert_frame(sub, depth + 1, steps);
+        }
+
+        let Some(ex) = &instruction.ex else {
+            // The operation failed, which ends the frame
+            break;
+        };
+
+        stack.truncate(stack.len().saturating_sub(opcodes::stack_inputs(&op)));
+        stack.extend(ex.push.iter().copied());
+
+        if let Some(delta) = ex.mem.as_ref().filter(|delta| !delta.data.is_empty()) {
+            let end = delta.off + delta.data.len();
+            if memory.len() < end {
+                memory.resize(end.div_ceil(32) * 32, 0);
+            }
+            memory[delta.off..end].copy_from_slice(&delta.data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    /// A vmTrace instruction that pushed the given values and wrote nothing to memory
+    fn instruction(pc: usize, op: &str, cost: u64, used: u64, push: &[u64]) -> Value {
+        json!({
+            "pc": pc,
+            "op": op,
+            "cost": cost,
+            "ex": {
+                "used": used,
+                "push": push.iter().map(|value| format!("{:#x}", value)).collect::<Vec<_>>(),
+                "mem": null,
+                "store": null
+            },
+            "sub": null
+        })
+    }
+
+    fn convert(ops: Vec<Value>) -> Vec<Value> {
+        let trace: VmTrace = serde_json::from_value(json!({ "code": "0x", "ops": ops })).unwrap();
+        let mut steps = vec![];
+        convert_frame(&trace, 1, &mut steps);
+        steps
+    }
+
+    #[test]
+    fn deltas_rebuild_the_struct_logs_geth_reports() {
+        let mut mstore = instruction(4, "MSTORE", 6, 88, &[]);
+        mstore["ex"]["mem"] = json!({ "off": 0, "data": format!("0x{:064x}", 0x2a) });
+        let mut mstore8 = instruction(9, "MSTORE8", 6, 76, &[]);
+        mstore8["ex"]["mem"] = json!({ "off": 33, "data": "0xff" });
+        let steps = convert(vec![
+            instruction(0, "PUSH1", 3, 97, &[0x2a]),
+            instruction(2, "PUSH1", 3, 94, &[0]),
+            mstore,
+            instruction(5, "PUSH1", 3, 85, &[0xff]),
+            instruction(7, "PUSH1", 3, 82, &[33]),
+            mstore8,
+            instruction(10, "STOP", 0, 76, &[]),
+        ]);
+
+        // debug_traceTransaction of the same code: stacks and memory are taken before each step
+        let word = format!("{:064x}", 0x2a);
+        let written = vec![word.clone(), format!("00ff{}", "00".repeat(30))];
+        let expected = json!([
+            { "pc": 0, "op": "PUSH1", "gas": 100, "gasCost": 3, "depth": 1,
+              "stack": [], "memory": [] },
+            { "pc": 2, "op": "PUSH1", "gas": 97, "gasCost": 3, "depth": 1,
+              "stack": ["0x2a"], "memory": [] },
+            { "pc": 4, "op": "MSTORE", "gas": 94, "gasCost": 6, "depth": 1,
+              "stack": ["0x2a", "0x0"], "memory": [] },
+            { "pc": 5, "op": "PUSH1", "gas": 88, "gasCost": 3, "depth": 1,
+              "stack": [], "memory": [word] },
+            { "pc": 7, "op": "PUSH1", "gas": 85, "gasCost": 3, "depth": 1,
+              "stack": ["0xff"], "memory": [word] },
+            { "pc": 9, "op": "MSTORE8", "gas": 82, "gasCost": 6, "depth": 1,
+              "stack": ["0xff", "0x21"], "memory": [word] },
+            { "pc": 10, "op": "STOP", "gas": 76, "gasCost": 0, "depth": 1,
+              "stack": [], "memory": written },
+        ]);
+        assert_eq!(Value::Array(steps), expected);
+    }
+
+    #[test]
+    fn sub_traces_run_one_level_deeper_before_the_caller_resumes() {
+        let mut ops: Vec<Value> =
+            (0..6).map(|pc| instruction(pc, "PUSH0", 2, 1000 - 2 * pc as u64, &[0])).collect();
+        let mut call = instruction(6, "STATICCALL", 200, 700, &[1]);
+        call["sub"] = json!({ "code": "0x00", "ops": [instruction(0, "STOP", 0, 500, &[])] });
+        ops.push(call);
+        ops.push(instruction(7, "STOP", 0, 700, &[]));
+
+        let steps = convert(ops);
+        let field = |name: &str| steps.iter().map(|step| step[name].clone()).collect::<Vec<_>>();
+        assert_eq!(field("op")[6..], [json!("STATICCALL"), json!("STOP"), json!("STOP")]);
+        assert_eq!(field("depth")[6..], [json!(1), json!(2), json!(1)]);
+        // STATICCALL consumes its six operands and pushes its result once the callee returns
+        assert_eq!(steps[6]["stack"].as_array().unwrap().len(), 6);
+        assert_eq!(steps[7]["stack"], json!([]));
+        assert_eq!(steps[8]["stack"], json!(["0x1"]));
+    }
+}
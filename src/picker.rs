@@ -0,0 +1,123 @@
+use color_eyre::eyre::{self, eyre};
+use crossterm::event::KeyCode::{self, Char};
+use ratatui::{
+    layout::{Alignment, Constraint},
+    style::{Color, Style, Stylize},
+    symbols::border,
+    text::Line,
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Cell, Row, Table, TableState,
+    },
+    Frame,
+};
+
+use crate::{
+    block::BlockData,
+    tui::{self, Event},
+};
+
+/// The picker only redraws on key presses, so a low frame rate is enough
+static PICKER_FPS: f64 = 10.0;
+
+/// Number of rows skipped by the page keys
+static PAGE_SIZE: usize = 10;
+
+/// Lets the user choose one of the transactions of a block, returns `None` if they quit instead
+pub async fn pick_transaction(block: &BlockData) -> Result<Option<usize>, eyre::Error> {
+    if block.transactions.is_empty() {
+        return Err(eyre!("Block {} has no transactions", block.number));
+    }
+
+    let mut tui = tui::Tui::new()?.frame_rate(PICKER_FPS);
+    let mut table_state = TableState::default().with_selected(Some(0));
+    let last = block.transactions.len() - 1;
+    let mut picked = None;
+
+    tui.enter()?;
+    while let Some(event) = tui.next().await {
+        let selected = table_state.selected().unwrap_or_default();
+        match event {
+            Event::Render => {
+                tui.draw(|frame| render(frame, block, &mut table_state))?;
+            }
+            Event::Key(key) => match key.code {
+                KeyCode::Up | Char('w') | Char('k') => {
+                    table_state.select(Some(selected.saturating_sub(1)))
+                }
+                KeyCode::Down | Char('s') | Char('j') => {
+                    table_state.select(Some((selected + 1).min(last)))
+                }
+                KeyCode::PageUp => table_state.select(Some(selected.saturating_sub(PAGE_SIZE))),
+                KeyCode::PageDown => table_state.select(Some((selected + PAGE_SIZE).min(last))),
+                KeyCode::Home => table_state.select(Some(0)),
+                KeyCode::End => table_state.select(Some(last)),
+                KeyCode::Enter => {
+                    picked = Some(selected);
+                    break;
+                }
+                KeyCode::Esc | Char('q') => break,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    tui.exit()?;
+
+    Ok(picked)
+}
+
+fn render(frame: &mut Frame, block: &BlockData, table_state: &mut TableState) {
+    let rows = block.transactions.iter().enumerate().map(|(index, block_transaction)| {
+        let transaction = &block_transaction.transaction;
+        let status = match block_transaction.success {
+            Some(true) => "ok".green(),
+            Some(false) => "failed".red(),
+            None => "-".into(),
+        };
+        Row::new(vec![
+            Cell::from(index.to_string()),
+            transaction.hash.to_string().into(),
+            transaction.from.to_string().into(),
+            transaction.to.map_or("create".to_string(), |to| to.to_string()).into(),
+            block_transaction.selector().unwrap_or("-".to_string()).into(),
+            block_transaction.gas_used.map_or("-".to_string(), |gas| gas.to_string()).into(),
+            Line::from(status).into(),
+        ])
+    });
+    let header = Row::new(vec!["#", "Hash", "From", "To", "Selector", "Gas used", "Status"])
+        .style(Style::default().bold());
+    let widths = [
+        Constraint::Length(4),
+        Constraint::Length(66),
+        Constraint::Length(42),
+        Constraint::Length(42),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(6),
+    ];
+
+    let title = Title::from(format!(" Block {} ", block.number).bold());
+    let instructions = Title::from(Line::from(vec![
+        " Up ".into(),
+        "<W>".green().bold(),
+        " Down ".into(),
+        "<S>".green().bold(),
+        " Open ".into(),
+        "<Enter>".blue().bold(),
+        " Quit ".into(),
+        "<Q> ".red().bold(),
+    ]));
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(
+            Block::default()
+                .title(title.alignment(Alignment::Center))
+                .title(instructions.alignment(Alignment::Center).position(Position::Bottom))
+                .borders(Borders::ALL)
+                .border_set(border::THICK),
+        )
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::LightCyan));
+
+    frame.render_stateful_widget(table, frame.size(), table_state);
+}
@@ -1,18 +1,297 @@
+use std::{
+    fmt,
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 use alloy::{
+    primitives::hex,
     providers::{ProviderBuilder, RootProvider},
-    transports::BoxTransport,
+    rpc::{
+        client::{ClientBuilder, IpcConnect, WsConnect},
+        json_rpc::{RequestPacket, ResponsePacket},
+    },
+    transports::{
+        http::{
+            reqwest::{
+                self,
+                header::{HeaderMap, HeaderName, AUTHORIZATION},
+            },
+            Http,
+        },
+        Authorization, BoxTransport, TransportError, TransportErrorKind, TransportFut,
+    },
 };
 use color_eyre::eyre::{self, eyre};
+use jsonwebtoken::{EncodingKey, Header};
+use tower::Service;
+use url::Url;
 
-pub struct HTTPProvider {
-    pub provider: RootProvider<BoxTransport>,
+/// How to reach and authenticate with a JSON-RPC endpoint
+#[derive(Debug, Clone, Default)]
+pub struct RpcConfig {
+    /// URL or IPC socket path of the endpoint
+    pub url: String,
+    /// Extra headers sent with every request
+    pub headers: Vec<(String, String)>,
+    /// File with the hex encoded secret used to sign a JWT for the `Authorization` header
+    pub jwt_secret: Option<PathBuf>,
+    /// Timeout of a single request
+    pub timeout: Option<Duration>,
+    /// Number of times a failed request is retried
+    pub retries: u32,
+    /// Delay before the first retry, doubled after every attempt
+    pub backoff: Duration,
+}
+
+impl RpcConfig {
+    pub fn new(url: &str) -> Self {
+        Self { url: url.to_string(), ..Default::default() }
+    }
+
+    /// Parses a `Name: value` header
+    pub fn parse_header(header: &str) -> Result<(String, String), eyre::Error> {
+        match header.split_once(':') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                Ok((name.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(eyre!("Invalid header {}, expected `Name: value`", header)),
+        }
+    }
+
+    /// Decoded secret of `jwt_secret`, if one is configured
+    fn secret(&self) -> Result<Option<Vec<u8>>, eyre::Error> {
+        let Some(path) = &self.jwt_secret else {
+            return Ok(None);
+        };
+        let secret = std::fs::read_to_string(path)
+            .map_err(|error| eyre!("Failed to read {}: {}", path.display(), error))?;
+        let secret = hex::decode(secret.trim())
+            .map_err(|error| eyre!("Invalid JWT secret in {}: {}", path.display(), error))?;
+        Ok(Some(secret))
+    }
+
+    /// `Authorization` header given explicitly with `--header`
+    fn authorization(&self) -> Option<String> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(AUTHORIZATION.as_str()))
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Runs a request with the configured timeout, retrying failed attempts with exponential
+    /// backoff
+    pub async fn request<T, E, F, Fut>(
+        &self,
+        description: &str,
+        mut request: F,
+    ) -> Result<T, eyre::Error>
+    where
+        E: fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let error = match self.timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, request()).await {
+                    Ok(Ok(value)) => return Ok(value),
+                    Ok(Err(error)) => eyre!("{} failed: {}", description, error),
+                    Err(_) => eyre!("{} timed out after {:?}", description, timeout),
+                },
+                None => match request().await {
+                    Ok(value) => return Ok(value),
+                    Err(error) => eyre!("{} failed: {}", description, error),
+                },
+            };
+
+            if attempt >= self.retries {
+                return Err(error);
+            }
+            let delay = self.backoff * 2u32.saturating_pow(attempt);
+            tracing::warn!("{}, retrying in {:?}", error, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Signs a JWT issued now, as the value of an `Authorization` header. Endpoints with Engine API
+/// style authentication refuse tokens issued more than a minute ago
+fn sign_jwt(secret: &[u8]) -> Result<String, eyre::Error> {
+    let issued_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let token = jsonwebtoken::encode(
+        &Header::default(),
+        &serde_json::json!({ "iat": issued_at }),
+        &EncodingKey::from_secret(secret),
+    )?;
+    Ok(format!("Bearer {}", token))
+}
+
+/// HTTP transport signing a fresh JWT for every request
+#[derive(Debug, Clone)]
+struct JwtHttp {
+    client: reqwest::Client,
+    url: Url,
+    secret: Arc<Vec<u8>>,
+}
+
+impl Service<RequestPacket> for JwtHttp {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let this = self.clone();
+        Box::pin(async move {
+            let authorization = sign_jwt(&this.secret)
+                .map_err(|error| TransportErrorKind::custom_str(&error.to_string()))?;
+            let response = this
+                .client
+                .post(this.url)
+                .header(AUTHORIZATION, authorization)
+                .json(&request)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(TransportErrorKind::custom)?;
+            let body = response.bytes().await.map_err(TransportErrorKind::custom)?;
+            serde_json::from_slice(&body)
+                .map_err(|error| TransportError::deser_err(error, String::from_utf8_lossy(&body)))
+        })
+    }
+}
+
+/// Transport used to reach a JSON-RPC endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transport {
+    /// `http://` and `https://` URLs
+    Http(Url),
+    /// `ws://` and `wss://` URLs
+    Ws(Url),
+    /// Path to an IPC socket
+    Ipc(PathBuf),
+}
+
+impl Transport {
+    /// Picks the transport from the scheme of the endpoint, anything without a scheme is treated
+    /// as an IPC socket path
+    pub fn parse(rpc: &str) -> Result<Self, eyre::Error> {
+        if !rpc.contains("://") {
+            let path = PathBuf::from(rpc);
+            if !path.exists() {
+                return Err(eyre!(
+                    "{} is not a URL, so it was treated as an IPC socket path, but it does not \
+                     exist",
+                    rpc
+                ));
+            }
+            return Ok(Transport::Ipc(path));
+        }
+
+        let url = Url::parse(rpc).map_err(|error| eyre!("Invalid RPC URL {}: {}", rpc, error))?;
+        match url.scheme() {
+            "http" | "https" => Ok(Transport::Http(url)),
+            "ws" | "wss" => Ok(Transport::Ws(url)),
+            "file" | "ipc" => Ok(Transport::Ipc(PathBuf::from(url.path()))),
+            scheme => Err(eyre!(
+                "Unsupported RPC scheme {}, expected http(s)://, ws(s):// or an IPC socket path",
+                scheme
+            )),
+        }
+    }
 }
 
-impl HTTPProvider {
-    pub async fn init(rpc: &str) -> Result<RootProvider<BoxTransport>, eyre::Error> {
-        match ProviderBuilder::new().on_builtin(rpc).await {
-            Ok(provider) => Ok(provider),
-            Err(error) => Err(eyre!(error)),
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Http(url) => write!(f, "HTTP ({})", url),
+            Transport::Ws(url) => write!(f, "WebSocket ({})", url),
+            Transport::Ipc(path) => write!(f, "IPC ({})", path.display()),
         }
     }
 }
+
+pub struct RpcProvider {
+    pub provider: RootProvider<BoxTransport>,
+}
+
+impl RpcProvider {
+    pub async fn init(config: &RpcConfig) -> Result<RootProvider<BoxTransport>, eyre::Error> {
+        let transport = Transport::parse(&config.url)?;
+        tracing::info!("Connecting to the RPC over {}", transport);
+
+        let secret = config.secret()?;
+        // WebSocket connections only authenticate once, when they are opened
+        let authorization = match &secret {
+            Some(secret) => Some(sign_jwt(secret)?),
+            None => config.authorization(),
+        };
+        let custom_headers = config
+            .headers
+            .iter()
+            .any(|(name, _)| !name.eq_ignore_ascii_case(AUTHORIZATION.as_str()));
+
+        let client = match &transport {
+            Transport::Http(url) => {
+                let mut headers = HeaderMap::new();
+                for (name, value) in &config.headers {
+                    headers.insert(HeaderName::from_bytes(name.as_bytes())?, value.parse()?);
+                }
+                if secret.is_some() {
+                    // A signed token takes precedence over a header given explicitly
+                    headers.remove(AUTHORIZATION);
+                }
+                let mut http_client = reqwest::Client::builder().default_headers(headers);
+                if let Some(timeout) = config.timeout {
+                    http_client = http_client.timeout(timeout);
+                }
+                let http_client = http_client.build()?;
+                let http = Http::with_client(http_client.clone(), url.clone());
+                let is_local = http.guess_local();
+                match secret {
+                    Some(secret) => {
+                        let secret = Arc::new(secret);
+                        let http = JwtHttp { client: http_client, url: url.clone(), secret };
+                        ClientBuilder::default().transport(http, is_local).boxed()
+                    }
+                    None => ClientBuilder::default().transport(http, is_local).boxed(),
+                }
+            }
+            Transport::Ws(_) if custom_headers => {
+                return Err(eyre!(
+                    "Only the Authorization header can be sent over {}, other headers need HTTP",
+                    transport
+                ))
+            }
+            Transport::Ipc(_) if custom_headers || authorization.is_some() => {
+                return Err(eyre!("Headers and JWT secrets cannot be sent over {}", transport))
+            }
+            Transport::Ws(url) => {
+                let mut connect = WsConnect::new(url.as_str());
+                if let Some(authorization) = authorization {
+                    connect = connect.with_auth(Authorization::raw(authorization));
+                }
+                ClientBuilder::default()
+                    .ws(connect)
+                    .await
+                    .map_err(|error| eyre!("Failed to connect over {}: {}", transport, error))?
+                    .boxed()
+            }
+            Transport::Ipc(path) => ClientBuilder::default()
+                .ipc(IpcConnect::new(path.clone()))
+                .await
+                .map_err(|error| eyre!("Failed to connect over {}: {}", transport, error))?
+                .boxed(),
+        };
+
+        Ok(ProviderBuilder::new().on_client(client))
+    }
+}
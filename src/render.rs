@@ -1,3 +1,6 @@
+use std::ops::Range;
+
+use alloy::primitives::{hex, Address, U256};
 use itertools::Itertools;
 use ratatui::{
     buffer::Buffer,
@@ -7,13 +10,17 @@ use ratatui::{
     text::Line,
     widgets::{
         block::{Position, Title},
-        Axis, Bar, BarChart, BarGroup, Block, Borders, Cell, Chart, Dataset, GraphType, Paragraph,
-        Row, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Table, TableState,
-        Widget,
+        Axis, Bar, BarGroup, Block, Borders, Cell, Chart, Clear, Dataset, GraphType,
+        Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Table,
+        TableState, Widget,
     },
 };
 
-use crate::state::{AppState, SlotStatus};
+use crate::{
+    calldata,
+    stack::{self, StackChange},
+    state::{AppState, FrameState, Panel, SlotStatus, TransactionState},
+};
 
 pub struct RenderData<'a> {
     pub area: Rect,
@@ -30,121 +37,182 @@ impl<'a> RenderData<'a> {
 
         for index in 0..indexes_length {
             let transaction_state = self.state.transaction_states[index].clone();
-            let layout = layouts[index];
+            let frames = transaction_state.viewed_frames(self.state.frame_view);
+            let frame_layouts = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Fill(1); frames.len()])
+                .split(layouts[index]);
 
-            let mut block: Block;
-
-            if indexes_length == 1 {
-                let title = Title::from(" Trill ".bold());
-                let instructions = Title::from(Line::from(vec![
-                    " Raw ".into(),
-                    "<F>".blue().bold(),
-                    " Up ".into(),
-                    "<W>".green().bold(),
-                    " Pause ".into(),
-                    "<Space>".yellow().bold(),
-                    " Down ".into(),
-                    "<S>".green().bold(),
-                    " Quit ".into(),
-                    "<Q> ".red().bold(),
-                ]));
-                block = Block::default()
-                    .title(title.alignment(Alignment::Center))
-                    .title(instructions.alignment(Alignment::Center).position(Position::Bottom))
-                    .borders(Borders::TOP)
-                    .border_set(border::THICK);
-            } else {
-                let title = Title::from(format!(" Transaction {} ", index).bold());
-                block = Block::default()
-                    .borders(Borders::ALL)
-                    .border_set(border::THICK)
-                    .title(title.alignment(Alignment::Center));
+            for (frame, layout) in frames.into_iter().zip(frame_layouts.iter().copied()) {
+                self.render_frame_memory(&transaction_state, frame, layout, index, indexes_length);
             }
+        }
+    }
 
-            let mut s = TableState::default();
-            let mut constraints: Vec<Constraint> = vec![];
-            let mut rows: Vec<Row> = vec![];
-            let height: usize = (layout.height - 2) as usize;
-
-            if self.state.display_memory_data {
-                let mut first_slot: usize = self.state.table_beginning_index as usize;
-
-                let operation_memory = &transaction_state.raw_data
-                    [(transaction_state.next_operation - 1) as usize]
-                    .memory;
+    fn render_frame_memory(
+        &mut self,
+        transaction_state: &TransactionState,
+        frame: &FrameState,
+        layout: Rect,
+        index: usize,
+        indexes_length: usize,
+    ) {
+        let mut block: Block;
 
-                if let Some(memory) = operation_memory {
-                    if first_slot >= memory.len() {
-                        first_slot = memory.len() - 1;
+        if indexes_length == 1 {
+            let title = Title::from(" Trill ".bold());
+            let instructions = Title::from(Line::from(vec![
+                " Raw ".into(),
+                "<F>".blue().bold(),
+                " Up ".into(),
+                "<W>".green().bold(),
+                " Pause ".into(),
+                "<Space>".yellow().bold(),
+                " Down ".into(),
+                "<S>".green().bold(),
+                " Caller ".into(),
+                "<P>".cyan().bold(),
+                " Current ".into(),
+                "<C>".cyan().bold(),
+                " All frames ".into(),
+                "<A>".cyan().bold(),
+                " Calls ".into(),
+                "<T>".cyan().bold(),
+                " Storage ".into(),
+                "<O>".cyan().bold(),
+                " Transient ".into(),
+                "<E>".cyan().bold(),
+                " Stack ".into(),
+                "<K>".cyan().bold(),
+                " Return data ".into(),
+                "<R>".cyan().bold(),
+                " Calldata ".into(),
+                "<D>".cyan().bold(),
+                " Jump ".into(),
+                "<:>".yellow().bold(),
+                " Quit ".into(),
+                "<Q> ".red().bold(),
+            ]));
+            block = Block::default()
+                .title(title.alignment(Alignment::Center))
+                .title(instructions.alignment(Alignment::Center).position(Position::Bottom))
+                .borders(Borders::TOP)
+                .border_set(border::THICK);
+        } else {
+            let title = Title::from(format!(" Transaction {} ", index).bold());
+            block = Block::default()
+                .borders(Borders::ALL)
+                .border_set(border::THICK)
+                .title(title.alignment(Alignment::Center));
+        }
+        let depth = Title::from(format!(" Depth {} ", frame.depth).cyan());
+        block = block.title(depth.alignment(Alignment::Left));
+
+        let mut s = TableState::default();
+        let mut constraints: Vec<Constraint> = vec![];
+        let mut rows: Vec<Row> = vec![];
+        let height: usize = (layout.height - 2) as usize;
+
+        // Watched words are drawn on a highlighted background
+        let node = transaction_state.call_tree.node_at(frame.last_operation, frame.depth);
+        let watched: Vec<_> = self
+            .state
+            .watchpoints
+            .iter()
+            .filter(|watchpoint| watchpoint.watches(node))
+            .map(|watchpoint| watchpoint.words.clone())
+            .collect();
+        let is_watched = |slot: usize| watched.iter().any(|words| words.contains(&slot));
+
+        if self.state.display_memory_data {
+            let mut first_slot: usize = self.state.table_beginning_index as usize;
+
+            let operation_memory = &transaction_state.raw_data[frame.last_operation].memory;
+
+            if let Some(memory) = operation_memory {
+                if first_slot >= memory.len() {
+                    first_slot = memory.len().saturating_sub(1);
+                }
+                let data = memory.iter().skip(first_slot).enumerate();
+                for (index, slot) in data {
+                    if index >= height {
+                        break;
                     }
-                    let data = memory.iter().skip(first_slot).enumerate();
-                    for (index, slot) in data {
-                        if index >= height {
-                            break;
-                        }
-                        let mut row: Vec<Cell> =
-                            vec![Cell::new((index + first_slot).to_string()).gray()];
-                        for chunk in &slot.chars().chunks(2) {
-                            let pair: String = chunk.collect();
-                            match transaction_state.slots[index + first_slot] {
-                                SlotStatus::Empty => row.push(Cell::new(pair).gray()),
-                                SlotStatus::Active => row.push(Cell::new(pair).green()),
-                                SlotStatus::Reading => row.push(Cell::new(pair).blue()),
-                                SlotStatus::Writing => row.push(Cell::new(pair).red()),
-                                SlotStatus::Unread => row.push(Cell::new(pair).magenta()),
-                                SlotStatus::Init => (),
-                            }
+                    let slot_number = index + first_slot;
+                    let number = Cell::new(slot_number.to_string()).gray();
+                    let mut row: Vec<Cell> = match is_watched(slot_number) {
+                        true => vec![number.on_yellow()],
+                        false => vec![number],
+                    };
+                    for (byte, chunk) in (&slot.chars().chunks(2)).into_iter().enumerate() {
+                        let pair: String = chunk.collect();
+                        // Colour each byte on its own when byte statuses are tracked
+                        let status = frame.byte_statuses.as_ref().map_or(
+                            frame.slots.get(slot_number).copied(),
+                            |bytes| bytes.get(slot_number * 32 + byte).copied(),
+                        );
+                        match status.unwrap_or(SlotStatus::Empty) {
+                            SlotStatus::Empty => row.push(Cell::new(pair).gray()),
+                            SlotStatus::Active => row.push(Cell::new(pair).green()),
+                            SlotStatus::Reading => row.push(Cell::new(pair).blue()),
+                            SlotStatus::Writing => row.push(Cell::new(pair).red()),
+                            SlotStatus::Unread => row.push(Cell::new(pair).magenta()),
+                            SlotStatus::Init => (),
                         }
-                        rows.push(Row::new(row));
                     }
+                    rows.push(Row::new(row));
                 }
+            }
 
-                constraints = vec![Constraint::Percentage(4)];
-                constraints.extend(vec![Constraint::Percentage(3); 32]);
-            } else {
-                let mut row: Vec<Cell> = vec![];
+            constraints = vec![Constraint::Percentage(4)];
+            constraints.extend(vec![Constraint::Percentage(3); 32]);
+        } else {
+            let mut row: Vec<Cell> = vec![];
 
-                let width: usize = match indexes_length {
-                    1 => (layout.width / 2) as usize,
-                    _ => (layout.width / 2 - 1) as usize,
-                };
+            let width: usize = match indexes_length {
+                1 => (layout.width / 2) as usize,
+                _ => (layout.width / 2 - 1) as usize,
+            };
 
-                let mut first_slot: usize = self.state.table_beginning_index as usize * width;
-                let mut range_ending = transaction_state.slots.len();
+            let mut first_slot: usize = self.state.table_beginning_index as usize * width;
+            let mut range_ending = frame.slots.len();
 
-                while first_slot > transaction_state.slots.len() {
-                    self.state.table_beginning_index -= 1;
-                    first_slot = self.state.table_beginning_index as usize * width;
-                }
+            while first_slot > frame.slots.len() {
+                self.state.table_beginning_index -= 1;
+                first_slot = self.state.table_beginning_index as usize * width;
+            }
 
-                if width * height < transaction_state.slots.len() - first_slot {
-                    range_ending = width * height;
-                }
+            if width * height < frame.slots.len() - first_slot {
+                range_ending = width * height;
+            }
 
-                for slot in first_slot..range_ending {
-                    match transaction_state.slots[slot] {
-                        SlotStatus::Empty => row.push(Cell::new("■").gray()),
-                        SlotStatus::Active => row.push(Cell::new("■").green()),
-                        SlotStatus::Reading => row.push(Cell::new("■").blue()),
-                        SlotStatus::Writing => row.push(Cell::new("■").red()),
-                        SlotStatus::Unread => row.push(Cell::new("■").magenta()),
-                        SlotStatus::Init => (),
-                    }
-                    if slot % width == width - 1 || slot == transaction_state.slots.len() - 1 {
-                        rows.push(Row::new(row.clone()));
-                        row.clear();
-                    }
+            for slot in first_slot..range_ending {
+                let cell = match is_watched(slot) {
+                    true => Cell::new("■").on_yellow(),
+                    false => Cell::new("■"),
+                };
+                match frame.slots[slot] {
+                    SlotStatus::Empty => row.push(cell.gray()),
+                    SlotStatus::Active => row.push(cell.green()),
+                    SlotStatus::Reading => row.push(cell.blue()),
+                    SlotStatus::Writing => row.push(cell.red()),
+                    SlotStatus::Unread => row.push(cell.magenta()),
+                    SlotStatus::Init => (),
+                }
+                if slot % width == width - 1 || slot == frame.slots.len() - 1 {
+                    rows.push(Row::new(row.clone()));
+                    row.clear();
                 }
-                constraints = vec![Constraint::Length(1); width];
             }
-
-            StatefulWidget::render(
-                Table::new(rows, constraints).block(block),
-                layout,
-                self.buf,
-                &mut s,
-            );
+            constraints = vec![Constraint::Length(1); width];
         }
+
+        StatefulWidget::render(
+            Table::new(rows, constraints).block(block),
+            layout,
+            self.buf,
+            &mut s,
+        );
     }
 
     fn render_transaction_box(&mut self, transaction_indexes: Vec<usize>, layouts: Vec<Rect>) {
@@ -178,17 +246,24 @@ impl<'a> RenderData<'a> {
                 ]),
                 Row::new(vec![
                     Cell::new("To").style(Style::new().gray().bold()),
-                    Cell::new(transaction.to.unwrap().to_string()).style(Style::new().gray()),
+                    Cell::new(transaction.to.map_or("-".to_string(), |to| to.to_string()))
+                        .style(Style::new().gray()),
                 ]),
                 Row::new(vec![
                     Cell::new("Block Hash").style(Style::new().gray().bold()),
-                    Cell::new(transaction.block_hash.unwrap().to_string())
-                        .style(Style::new().gray()),
+                    Cell::new(
+                        transaction.block_hash.map_or("-".to_string(), |hash| hash.to_string()),
+                    )
+                    .style(Style::new().gray()),
                 ]),
                 Row::new(vec![
                     Cell::new("Block Number").style(Style::new().gray().bold()),
-                    Cell::new(transaction.block_number.unwrap().to_string())
-                        .style(Style::new().gray()),
+                    Cell::new(
+                        transaction
+                            .block_number
+                            .map_or("-".to_string(), |number| number.to_string()),
+                    )
+                    .style(Style::new().gray()),
                 ]),
                 Row::new(vec![Cell::new("Success").style(Style::new().gray().bold()), success]),
                 Row::new(vec![
@@ -225,6 +300,8 @@ impl<'a> RenderData<'a> {
 
             let mut info_vec = vec![];
             let op = &transaction_state.operation_to_render;
+            let expansion_total =
+                transaction_state.expansion_dataset.last().map_or(0.0, |point| point.1);
             let operation_code = match SlotStatus::from_opcode(&op.operation) {
                 SlotStatus::Reading => Cell::new(op.operation.text()).blue(),
                 SlotStatus::Writing => Cell::new(op.operation.text()).red(),
@@ -237,6 +314,22 @@ impl<'a> RenderData<'a> {
                     Cell::new("Gas cost").style(Style::new().gray()),
                     Cell::new(op.gas_cost.to_string()).style(Style::new().gray()),
                 ]),
+                Row::new(vec![
+                    Cell::new("Base cost").style(Style::new().gray()),
+                    Cell::new(op.gas_cost.saturating_sub(op.expansion_cost).to_string())
+                        .style(Style::new().gray()),
+                ]),
+                Row::new(vec![
+                    Cell::new("Expansion cost").style(Style::new().gray()),
+                    match op.expansion_cost {
+                        0 => Cell::new("0").style(Style::new().gray()),
+                        cost => Cell::new(cost.to_string()).style(Style::new().yellow()),
+                    },
+                ]),
+                Row::new(vec![
+                    Cell::new("Total expansion").style(Style::new().gray()),
+                    Cell::new(expansion_total.to_string()).style(Style::new().yellow()),
+                ]),
                 Row::new(vec![
                     Cell::new("Gas remaining").style(Style::new().gray()),
                     Cell::new(op.remaining_gas.to_string()).style(Style::new().gray()),
@@ -280,6 +373,499 @@ impl<'a> RenderData<'a> {
         }
     }
 
+    fn render_call_tree(&mut self, layout: Rect) {
+        let transaction_state = &self.state.transaction_states[0];
+        let collapsed = &transaction_state.collapsed_calls;
+        let visible = transaction_state.call_tree.visible(collapsed);
+
+        let rows = visible.iter().map(|index| {
+            let node = &transaction_state.call_tree.nodes[*index];
+            let marker = match (node.children.is_empty(), collapsed.contains(index)) {
+                (true, _) => " ",
+                (false, true) => "▸",
+                (false, false) => "▾",
+            };
+            let status = match node.success {
+                true => "ok".green(),
+                false => "failed".red(),
+            };
+            let indent = "  ".repeat(node.depth as usize - 1);
+            let row = Row::new(vec![
+                Cell::from(format!("{}{} {}", indent, marker, node.kind)),
+                node.address.map_or("-".to_string(), |address| address.to_string()).into(),
+                node.selector.clone().unwrap_or("-".to_string()).into(),
+                node.value.map_or("-".to_string(), |value| value.to_string()).into(),
+                node.gas.to_string().into(),
+                Line::from(status).into(),
+            ]);
+            match transaction_state.scope == Some(*index) {
+                true => row.style(Style::new().yellow()),
+                false => row,
+            }
+        });
+        let header = Row::new(vec!["Call", "Target", "Selector", "Value", "Gas", "Status"])
+            .style(Style::default().bold());
+        let widths = [
+            Constraint::Min(16),
+            Constraint::Length(42),
+            Constraint::Length(10),
+            Constraint::Fill(1),
+            Constraint::Length(10),
+            Constraint::Length(6),
+        ];
+
+        let title = Title::from(" Call tree ".bold());
+        let instructions = Title::from(Line::from(vec![
+            " Move ".into(),
+            "<Up/Down>".yellow().bold(),
+            " Fold ".into(),
+            "<-/+>".yellow().bold(),
+            " Jump ".into(),
+            "<Enter>".blue().bold(),
+            " Close ".into(),
+            "<T> ".cyan().bold(),
+        ]));
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(
+                Block::default()
+                    .title(title.alignment(Alignment::Center))
+                    .title(instructions.alignment(Alignment::Center).position(Position::Bottom))
+                    .borders(Borders::ALL)
+                    .border_set(border::THICK),
+            )
+            .highlight_style(Style::default().fg(Color::Black).bg(Color::LightCyan));
+        let selected = visible.iter().position(|index| *index == transaction_state.selected_call);
+        let mut table_state = TableState::default().with_selected(selected);
+
+        StatefulWidget::render(table, layout, self.buf, &mut table_state);
+    }
+
+    fn render_storage(&mut self, layout: Rect) {
+        let divided_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(layout);
+        let transaction_state = &self.state.transaction_states[0];
+        let storage = &transaction_state.storage;
+        let processed = (transaction_state.next_operation as usize).min(storage.read_dataset.len());
+        let current = processed.checked_sub(1);
+        let hex =
+            |value: Option<U256>| value.map_or("?".to_string(), |value| format!("{:#x}", value));
+
+        let rows = storage.slots(current.unwrap_or_default()).into_iter().map(|slot| {
+            let access = match slot.last.cold {
+                true => format!("cold {}", slot.last.gas_cost),
+                false => format!("warm {}", slot.last.gas_cost),
+            };
+            let row = Row::new(vec![
+                Cell::from(slot.address.map_or("-".to_string(), |address| address.to_string())),
+                format!("{:#x}", slot.key).into(),
+                hex(slot.last.old).into(),
+                hex(slot.value).into(),
+                slot.reads.to_string().into(),
+                slot.writes.to_string().into(),
+                access.into(),
+            ]);
+            // Same colours as the memory slots: the slot the current operation touches is being
+            // read or written, written slots stay marked
+            match (Some(slot.last.operation) == current, slot.last.status, slot.writes) {
+                (true, SlotStatus::Reading, _) => row.blue(),
+                (true, _, _) => row.red(),
+                (false, _, 0) => row.green(),
+                (false, _, _) => row.magenta(),
+            }
+        });
+        let header = Row::new(vec!["Contract", "Slot", "Old", "Value", "R", "W", "Access"])
+            .style(Style::default().bold());
+        let widths = [
+            Constraint::Length(42),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(10),
+        ];
+        let title = Title::from(" Storage ".bold());
+        let table = Table::new(rows, widths).header(header).block(
+            Block::default()
+                .title(title.alignment(Alignment::Center))
+                .borders(Borders::ALL)
+                .border_set(border::THICK),
+        );
+        let mut table_state = TableState::default();
+        StatefulWidget::render(table, divided_layout[0], self.buf, &mut table_state);
+
+        let read_dataset = &storage.read_dataset[..processed];
+        let write_dataset = &storage.write_dataset[..processed];
+        let datasets = vec![
+            Dataset::default()
+                .name("SSTORE")
+                .marker(symbols::Marker::Dot)
+                .graph_type(GraphType::Line)
+                .style(Style::default().red())
+                .data(write_dataset),
+            Dataset::default()
+                .name("SLOAD")
+                .marker(symbols::Marker::Dot)
+                .graph_type(GraphType::Line)
+                .style(Style::default().blue())
+                .data(read_dataset),
+        ];
+        let x_axis_upper_bound = processed as f64;
+        let y_axis_upper_bound = read_dataset
+            .last()
+            .zip(write_dataset.last())
+            .map_or(0.0, |(read, write)| read.1.max(write.1));
+        let x_axis = Axis::default()
+            .style(Style::default().white())
+            .bounds([0.0, x_axis_upper_bound])
+            .labels(vec!["0".into(), x_axis_upper_bound.to_string().into()]);
+        let y_axis = Axis::default()
+            .style(Style::default().white())
+            .bounds([0.0, y_axis_upper_bound])
+            .labels(vec!["0".into(), y_axis_upper_bound.to_string().into()]);
+        let block = Block::default()
+            .title(Title::from(" SLOADs & SSTOREs ".bold()).alignment(Alignment::Center))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+        let chart = Chart::new(datasets).x_axis(x_axis).y_axis(y_axis).block(block);
+
+        Widget::render(chart, divided_layout[1], self.buf);
+    }
+
+    fn render_transient_storage(&mut self, layout: Rect) {
+        let divided_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(layout);
+        let transaction_state = &self.state.transaction_states[0];
+        let storage = &transaction_state.transient_storage;
+        let processed = transaction_state.next_operation as usize;
+        let current = processed.checked_sub(1);
+        let hex =
+            |value: Option<U256>| value.map_or("?".to_string(), |value| format!("{:#x}", value));
+        let address = |address: Option<Address>| {
+            address.map_or("-".to_string(), |address| address.to_string())
+        };
+
+        // Transient storage is cleared once the transaction ends
+        let ended = processed >= transaction_state.raw_data.len();
+        let slots = match ended {
+            true => vec![],
+            false => storage.slots(current.unwrap_or_default()),
+        };
+        let rows = slots.into_iter().map(|slot| {
+            let row = Row::new(vec![
+                Cell::from(address(slot.address)),
+                format!("{:#x}", slot.key).into(),
+                hex(slot.value).into(),
+                slot.reads.to_string().into(),
+                slot.writes.to_string().into(),
+            ]);
+            match (Some(slot.last.operation) == current, slot.last.status, slot.writes) {
+                (true, SlotStatus::Reading, _) => row.blue(),
+                (true, _, _) => row.red(),
+                (false, _, 0) => row.green(),
+                (false, _, _) => row.magenta(),
+            }
+        });
+        let header =
+            Row::new(vec!["Contract", "Key", "Value", "R", "W"]).style(Style::default().bold());
+        let widths = [
+            Constraint::Length(42),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Length(3),
+            Constraint::Length(3),
+        ];
+        let title = match ended {
+            true => Title::from(" Transient storage (cleared) ".bold()),
+            false => Title::from(" Transient storage ".bold()),
+        };
+        let table = Table::new(rows, widths).header(header).block(
+            Block::default()
+                .title(title.alignment(Alignment::Center))
+                .borders(Borders::ALL)
+                .border_set(border::THICK),
+        );
+        let mut table_state = TableState::default();
+        StatefulWidget::render(table, divided_layout[0], self.buf, &mut table_state);
+
+        // Every access so far in execution order, the latest one at the bottom
+        let accesses: Vec<_> = storage
+            .accesses
+            .iter()
+            .take_while(|access| Some(access.operation) <= current)
+            .collect();
+        let rows = accesses.iter().map(|access| {
+            let (op, style) = match access.status {
+                SlotStatus::Reading => ("TLOAD", Style::new().blue()),
+                _ => ("TSTORE", Style::new().red()),
+            };
+            Row::new(vec![
+                Cell::from(access.operation.to_string()),
+                op.into(),
+                address(access.address).into(),
+                format!("{:#x}", access.key).into(),
+                hex(access.value).into(),
+            ])
+            .style(style)
+        });
+        let header = Row::new(vec!["Step", "Op", "Contract", "Key", "Value"])
+            .style(Style::default().bold());
+        let widths = [
+            Constraint::Length(8),
+            Constraint::Length(6),
+            Constraint::Length(42),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ];
+        let table = Table::new(rows, widths).header(header).block(
+            Block::default()
+                .title(Title::from(" Access order ".bold()).alignment(Alignment::Center))
+                .borders(Borders::ALL)
+                .border_set(border::THICK),
+        );
+        let height = divided_layout[1].height.saturating_sub(3) as usize;
+        let mut table_state =
+            TableState::default().with_offset(accesses.len().saturating_sub(height));
+        StatefulWidget::render(table, divided_layout[1], self.buf, &mut table_state);
+    }
+
+    fn render_return_data(&mut self, layout: Rect) {
+        let divided_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(7), Constraint::Fill(1)])
+            .split(layout);
+        let transaction_state = &self.state.transaction_states[0];
+        let current = transaction_state.next_operation.checked_sub(1).map(|op| op as usize);
+        let buffer = current.and_then(|operation| {
+            transaction_state.return_data.buffer_at(
+                &transaction_state.call_tree,
+                &transaction_state.raw_data,
+                operation,
+            )
+        });
+        let block = |title: &'static str| {
+            Block::default()
+                .title(Title::from(title.bold()).alignment(Alignment::Center))
+                .borders(Borders::ALL)
+                .border_set(border::THICK)
+        };
+
+        let Some(buffer) = buffer else {
+            Widget::render(
+                Paragraph::new("No sub call has returned to this frame yet".gray())
+                    .block(block(" Return data ")),
+                layout,
+                self.buf,
+            );
+            return;
+        };
+        let operation = current.unwrap_or_default();
+        let node = &transaction_state.call_tree.nodes[buffer.call];
+        let copied = buffer.copied(operation);
+        let copies = buffer.copies.iter().filter(|(copy, _)| *copy <= operation).count();
+        // The output range of calls counts as a copy made when the call returns
+        let copies = match node.kind.is_create() {
+            true => copies,
+            false => copies.saturating_sub(1),
+        };
+        let status = match buffer.size {
+            None => Cell::new("unknown size").gray(),
+            Some(0) => Cell::new("empty").gray(),
+            Some(size) if size > copied => {
+                Cell::new(format!("{} bytes never copied", size - copied)).yellow().bold()
+            }
+            Some(_) => Cell::new("fully copied").green(),
+        };
+        let gray = |text: &str| Cell::new(text.to_string()).style(Style::new().gray().bold());
+        let rows = vec![
+            Row::new(vec![
+                gray("Call"),
+                Cell::new(format!(
+                    "{} {}",
+                    node.kind,
+                    node.address.map_or("-".to_string(), |address| address.to_string())
+                )),
+            ]),
+            Row::new(vec![gray("Returned at"), Cell::new(buffer.operation.to_string())]),
+            Row::new(vec![
+                gray("Size"),
+                Cell::new(buffer.size.map_or("?".to_string(), |size| format!("{} bytes", size))),
+            ]),
+            Row::new(vec![
+                gray("Copied"),
+                Cell::new(format!("{} bytes, {} RETURNDATACOPY", copied, copies)),
+            ]),
+            Row::new(vec![gray("Status"), status]),
+        ];
+        let table = Table::new(rows, [Constraint::Length(12), Constraint::Fill(1)])
+            .block(block(" Return data "));
+        Widget::render(table, divided_layout[0], self.buf);
+
+        // Copied bytes are green, the ones never copied gray
+        let lines: Vec<Line> = match &buffer.data {
+            None => vec![Line::from("Contents not in the trace".gray())],
+            Some(data) => data
+                .chunks(32)
+                .enumerate()
+                .map(|(word, bytes)| {
+                    let mut spans = vec![format!("{:#06x} ", word * 32).gray()];
+                    spans.extend(bytes.iter().enumerate().map(|(index, byte)| {
+                        let text = format!("{:02x}", byte);
+                        match buffer.is_copied(operation, word * 32 + index) {
+                            true => text.green(),
+                            false => text.gray(),
+                        }
+                    }));
+                    Line::from(spans)
+                })
+                .collect(),
+        };
+        Widget::render(
+            Paragraph::new(lines).block(block(" Buffer ")),
+            divided_layout[1],
+            self.buf,
+        );
+    }
+
+    fn render_calldata(&mut self, layout: Rect) {
+        let divided_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(layout);
+        let transaction_state = &self.state.transaction_states[0];
+        let operation = transaction_state.next_operation.saturating_sub(1) as usize;
+        let step = transaction_state.raw_data.get(operation);
+        let node = step.map_or(0, |step| {
+            transaction_state.call_tree.node_at(operation, step.depth)
+        });
+        let data = calldata::calldata(transaction_state, node).unwrap_or_default();
+        let decoded = match transaction_state.call_tree.nodes.get(node) {
+            Some(call) if call.kind.is_create() => None,
+            // Transactions without a recipient deploy their input
+            _ if node == 0 && transaction_state.transaction.to.is_none() => None,
+            _ => self.state.abi.decode(&data),
+        };
+        // Bytes of the calldata the current operation reads
+        let read = step.and_then(|step| {
+            calldata::read_range(&step.op, step.stack.as_deref().unwrap_or_default())
+        });
+        let is_read = |range: &Range<usize>| {
+            read.as_ref().is_some_and(|read| read.start < range.end && range.start < read.end)
+        };
+        let block = |title: String| {
+            Block::default()
+                .title(Title::from(title.bold()).alignment(Alignment::Center))
+                .borders(Borders::ALL)
+                .border_set(border::THICK)
+        };
+
+        let title = match &decoded {
+            Some(decoded) => format!(" {} ", decoded.signature),
+            None => " Calldata ".to_string(),
+        };
+        match &decoded {
+            Some(decoded) => {
+                let rows = decoded.arguments.iter().map(|argument| {
+                    let bytes = match &argument.tail {
+                        Some(tail) => format!(
+                            "{}-{}, {}-{}",
+                            argument.head.start, argument.head.end, tail.start, tail.end
+                        ),
+                        None => format!("{}-{}", argument.head.start, argument.head.end),
+                    };
+                    let row = Row::new(vec![
+                        Cell::from(argument.name.clone()),
+                        argument.ty.clone().into(),
+                        argument.value.clone().into(),
+                        bytes.into(),
+                    ]);
+                    match read.as_ref().is_some_and(|read| argument.overlaps(read)) {
+                        true => row.blue().bold(),
+                        false => row,
+                    }
+                });
+                let header = Row::new(vec!["Argument", "Type", "Value", "Bytes"])
+                    .style(Style::default().bold());
+                let widths = [
+                    Constraint::Length(16),
+                    Constraint::Length(10),
+                    Constraint::Fill(1),
+                    Constraint::Length(16),
+                ];
+                let table = Table::new(rows, widths).header(header).block(block(title));
+                Widget::render(table, divided_layout[0], self.buf);
+            }
+            None => {
+                let text = match (data.get(..4), self.state.abi.functions.is_empty()) {
+                    (None, _) => "No function selector".to_string(),
+                    (Some(_), true) => "Pass --abi to decode the arguments".to_string(),
+                    (Some(selector), false) => {
+                        format!("Selector 0x{} is not in the ABIs", hex::encode(selector))
+                    }
+                };
+                Widget::render(
+                    Paragraph::new(text.gray()).block(block(title)),
+                    divided_layout[0],
+                    self.buf,
+                );
+            }
+        }
+
+        // Selector first, then the 32-byte words labeled with the arguments they encode. Bytes
+        // the current operation reads are blue
+        let mut chunks = vec![(0..data.len().min(4))];
+        chunks.extend((4..data.len()).step_by(32).map(|start| start..(start + 32).min(data.len())));
+        let lines: Vec<Line> = chunks
+            .into_iter()
+            .filter(|chunk| !chunk.is_empty())
+            .map(|chunk| {
+                let mut spans = vec![format!("{:#06x} ", chunk.start).gray()];
+                spans.extend(chunk.clone().map(|index| {
+                    let text = format!("{:02x}", data[index]);
+                    match is_read(&(index..index + 1)) {
+                        true => text.blue().bold(),
+                        false => text.into(),
+                    }
+                }));
+                let names = decoded
+                    .as_ref()
+                    .map(|decoded| decoded.arguments_in(&chunk).join(", "))
+                    .unwrap_or_default();
+                spans.push(format!(" {}", names).yellow());
+                Line::from(spans)
+            })
+            .collect();
+        let title = match (step, &read) {
+            (Some(step), Some(read)) => {
+                let names = decoded
+                    .as_ref()
+                    .map(|decoded| decoded.arguments_in(read).join(", "))
+                    .filter(|names| !names.is_empty())
+                    .map_or(String::new(), |names| format!(": {}", names));
+                // The memory words a CALLDATACOPY turns red hold these arguments
+                let destination = match step.op.as_str() {
+                    "CALLDATACOPY" => step
+                        .stack
+                        .as_ref()
+                        .and_then(|stack| stack.last())
+                        .map_or(String::new(), |offset| format!(" into memory {:#x}", offset)),
+                    _ => String::new(),
+                };
+                format!(
+                    " {} reads bytes {}-{}{}{} ",
+                    step.op, read.start, read.end, destination, names
+                )
+            }
+            _ => format!(" {} bytes ", data.len()),
+        };
+        Widget::render(Paragraph::new(lines).block(block(title)), divided_layout[1], self.buf);
+    }
+
     fn render_operation_history(&mut self, transaction_indexes: Vec<usize>, layouts: Vec<Rect>) {
         let indexes_length = transaction_indexes.len();
         if indexes_length != layouts.len() {
@@ -305,8 +891,8 @@ impl<'a> RenderData<'a> {
                 .border_set(border::THICK);
 
             let items: Vec<Line> = transaction_state
-                .operation_codes
-                .iter()
+                .history()
+                .into_iter()
                 .map(|op| match SlotStatus::from_opcode(op) {
                     SlotStatus::Reading => Line::from(op.text()).style(Style::new().blue()),
                     SlotStatus::Writing => Line::from(op.text()).style(Style::new().red()),
@@ -358,7 +944,11 @@ impl<'a> RenderData<'a> {
             let transaction_state = &self.state.transaction_states[index];
             let divided_space = Layout::default()
                 .direction(Direction::Horizontal)
-                .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
+                .constraints(vec![
+                    Constraint::Ratio(1, 3),
+                    Constraint::Ratio(1, 3),
+                    Constraint::Ratio(1, 3),
+                ])
                 .split(layouts[index]);
 
             let write_title = Title::from(" Writes ".bold().red());
@@ -431,6 +1021,40 @@ impl<'a> RenderData<'a> {
 
             Widget::render(write_chart, divided_space[0], self.buf);
             Widget::render(read_chart, divided_space[1], self.buf);
+
+            // Gas is on a different scale than the access counts, so it gets its own chart
+            let expansion_total =
+                transaction_state.expansion_dataset.last().map_or(0.0, |point| point.1);
+            let expansion_dataset = vec![Dataset::default()
+                .name("Expansion gas")
+                .marker(symbols::Marker::Dot)
+                .graph_type(GraphType::Line)
+                .style(Style::default().yellow())
+                .data(&transaction_state.expansion_dataset)];
+            let expansion_x_axis = Axis::default()
+                .style(Style::default().white())
+                .bounds([0.0, transaction_state.expansion_dataset.len() as f64])
+                .labels(vec![
+                    "0".into(),
+                    transaction_state.expansion_dataset.len().to_string().into(),
+                ]);
+            let expansion_y_axis = Axis::default()
+                .style(Style::default().white())
+                .bounds([0.0, expansion_total])
+                .labels(vec!["0".into(), expansion_total.ceil().to_string().into()]);
+            let expansion_chart = Chart::new(expansion_dataset)
+                .x_axis(expansion_x_axis)
+                .y_axis(expansion_y_axis)
+                .block(
+                    Block::default()
+                        .title(
+                            Title::from(" Expansion gas ".bold().yellow())
+                                .alignment(Alignment::Center),
+                        )
+                        .borders(Borders::ALL)
+                        .border_set(border::THICK),
+                );
+            Widget::render(expansion_chart, divided_space[2], self.buf);
         }
     }
 
@@ -443,26 +1067,46 @@ impl<'a> RenderData<'a> {
 
         for index in 0..indexes_length {
             let transaction_state = &self.state.transaction_states[index];
-            let bar_value = match &transaction_state.operation_to_render.stack {
-                Some(stack) => stack.len(),
-                None => 0,
+            let items = match transaction_state.next_operation.checked_sub(1) {
+                Some(operation) => stack::inspect(transaction_state, operation as usize),
+                None => vec![],
             };
-
-            let max_value = match bar_value > 10 {
-                True => bar_value + 5,
-                False => 10,
-            };
-
-            let chart = BarChart::default()
-                .block(Block::bordered().title("Stack"))
-                .bar_width(1)
-                .bar_style(Style::new().white())
-                .value_style(Style::new().yellow().bold())
-                .label_style(Style::new().white())
-                .direction(Direction::Horizontal)
-                .data(&[("Size", bar_value as u64)])
-                .max(max_value as u64);
-            Widget::render(chart, layouts[index], self.buf);
+            let size = items.iter().filter(|item| item.depth.is_some()).count();
+
+            let rows = items.iter().map(|item| {
+                let row = Row::new(vec![
+                    Cell::from(item.depth.map_or("-".to_string(), |depth| depth.to_string())),
+                    Cell::from(item.operand.unwrap_or_default()).yellow(),
+                    format!("{:#x}", item.value).into(),
+                    item.value.to_string().into(),
+                ]);
+                match item.change {
+                    StackChange::Kept => row,
+                    StackChange::Pushed => row.green(),
+                    StackChange::Popped => row.red().crossed_out(),
+                }
+            });
+            let header =
+                Row::new(vec!["#", "Operand", "Hex", "Decimal"]).style(Style::default().bold());
+            let widths = [
+                Constraint::Length(4),
+                Constraint::Length(12),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ];
+            let table = Table::new(rows, widths).header(header).block(
+                Block::default()
+                    .title(
+                        Title::from(format!(" Stack ({}) ", size).bold())
+                            .alignment(Alignment::Center),
+                    )
+                    .borders(Borders::ALL)
+                    .border_set(border::THICK),
+            );
+            let offset = (self.state.stack_vertical_scroll as usize)
+                .min(items.len().saturating_sub(1));
+            let mut table_state = TableState::default().with_offset(offset);
+            StatefulWidget::render(table, layouts[index], self.buf, &mut table_state);
         }
     }
 
@@ -519,6 +1163,27 @@ impl<'a> RenderData<'a> {
 
         let (transaction_box, opcode_box) = (info_boxes[0], info_boxes[1]);
 
+        let memory_box = match self.state.panel {
+            Some(panel) => {
+                let divided_memory_layout = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .split(memory_box);
+                match panel {
+                    Panel::CallTree => self.render_call_tree(divided_memory_layout[0]),
+                    Panel::Storage => self.render_storage(divided_memory_layout[0]),
+                    Panel::TransientStorage => {
+                        self.render_transient_storage(divided_memory_layout[0])
+                    }
+                    Panel::Stack => self.render_stack(vec![0], vec![divided_memory_layout[0]]),
+                    Panel::ReturnData => self.render_return_data(divided_memory_layout[0]),
+                    Panel::Calldata => self.render_calldata(divided_memory_layout[0]),
+                }
+                divided_memory_layout[1]
+            }
+            None => memory_box,
+        };
+
         self.render_memory(vec![0], vec![memory_box]);
         self.render_transaction_box(vec![0_usize], vec![transaction_box]);
         self.render_current_operation_box(vec![0], vec![opcode_box]);
@@ -577,12 +1242,12 @@ impl<'a> RenderData<'a> {
 
         let divided_info0_layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Fill(5), Constraint::Fill(2)])
+            .constraints(vec![Constraint::Fill(1), Constraint::Fill(1)])
             .split(divided_memory0_layout[0]);
 
         let divided_info1_layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Fill(5), Constraint::Fill(2)])
+            .constraints(vec![Constraint::Fill(1), Constraint::Fill(1)])
             .split(divided_memory1_layout[0]);
 
         let (opcode0_box, opcode1_box) = (divided_info0_layout[0], divided_info1_layout[0]);
@@ -706,10 +1371,24 @@ impl<'a> RenderData<'a> {
         Widget::render(chart, layout, self.buf);
     }
 
+    /// Draws the command prompt, or the last message, over the bottom line
+    fn render_prompt(&mut self) {
+        let line = match (&self.state.command, &self.state.message) {
+            (Some(command), _) => Line::from(vec![":".yellow().bold(), command.clone().into()]),
+            (None, Some(message)) => Line::from(message.clone().yellow()),
+            (None, None) => return,
+        };
+        let layout = Rect { y: self.area.bottom().saturating_sub(1), height: 1, ..self.area };
+
+        Widget::render(Clear, layout, self.buf);
+        Widget::render(Paragraph::new(line), layout, self.buf);
+    }
+
     pub fn render_all(&mut self) {
         match self.state.mode {
             crate::state::AppMode::Versus => self.render_versus(),
             crate::state::AppMode::Normal => self.render_normal(),
         }
+        self.render_prompt();
     }
 }
@@ -0,0 +1,442 @@
+use std::{collections::HashMap, future::Future};
+
+use alloy::{
+    primitives::{address, Address, Bytes, TxHash, B256, U256},
+    providers::{Provider, RootProvider},
+    rpc::types::{
+        eth::{BlockId, BlockNumberOrTag, BlockTransactions, Header, Transaction},
+        trace::geth::StructLog,
+    },
+    transports::BoxTransport,
+};
+use clap::ValueEnum;
+use color_eyre::eyre::{self, eyre};
+use revm::{
+    db::{CacheDB, EmptyDB},
+    inspector_handle_register,
+    inspectors::NoOpInspector,
+    interpreter::{opcode::OpCode, Interpreter},
+    primitives::{AccountInfo, BlockEnv, Bytecode, SpecId, TransactTo, TxEnv},
+    Database, Evm, EvmContext, Inspector,
+};
+use serde::{Deserialize as _, Deserializer};
+use serde_derive::Deserialize;
+use serde_json::json;
+
+use crate::{memory, number::parse_number, provider::RpcConfig, source::TraceData};
+
+/// Address the playground bytecode is deployed at
+static PLAYGROUND_ADDRESS: Address = address!("c0dec0dec0dec0dec0dec0dec0dec0dec0dec0de");
+/// Default sender of playground calls
+pub static PLAYGROUND_CALLER: Address = address!("ca11e7ca11e7ca11e7ca11e7ca11e7ca11e7ca11");
+/// Gas limit of playground calls
+static PLAYGROUND_GAS_LIMIT: u64 = 30_000_000;
+
+/// Hardforks the embedded EVM can execute with
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Hardfork {
+    Frontier,
+    Homestead,
+    Tangerine,
+    SpuriousDragon,
+    Byzantium,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Merge,
+    Shanghai,
+    Cancun,
+}
+
+impl Hardfork {
+    pub fn spec_id(self) -> SpecId {
+        match self {
+            Hardfork::Frontier => SpecId::FRONTIER,
+            Hardfork::Homestead => SpecId::HOMESTEAD,
+            Hardfork::Tangerine => SpecId::TANGERINE,
+            Hardfork::SpuriousDragon => SpecId::SPURIOUS_DRAGON,
+            Hardfork::Byzantium => SpecId::BYZANTIUM,
+            Hardfork::Petersburg => SpecId::PETERSBURG,
+            Hardfork::Istanbul => SpecId::ISTANBUL,
+            Hardfork::Berlin => SpecId::BERLIN,
+            Hardfork::London => SpecId::LONDON,
+            Hardfork::Merge => SpecId::MERGE,
+            Hardfork::Shanghai => SpecId::SHANGHAI,
+            Hardfork::Cancun => SpecId::CANCUN,
+        }
+    }
+}
+
+/// An account of the JSON state that seeds the playground, in the genesis `alloc` format
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SeedAccount {
+    #[serde(default)]
+    pub balance: U256,
+    #[serde(default, deserialize_with = "quantity")]
+    pub nonce: u64,
+    #[serde(default)]
+    pub code: Bytes,
+    #[serde(default)]
+    pub storage: HashMap<U256, U256>,
+}
+
+/// Accepts a number as well as the decimal or hex string genesis files usually hold
+fn quantity<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Quantity {
+        Number(u64),
+        Text(String),
+    }
+    match Quantity::deserialize(deserializer)? {
+        Quantity::Number(number) => Ok(number),
+        Quantity::Text(text) => parse_number(&text).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Records every executed step in the format of geth's default struct logger
+#[derive(Debug, Default)]
+pub struct StructLogInspector {
+    steps: Vec<serde_json::Value>,
+    /// Remaining gas before the step that is currently executing
+    gas_before_step: u64,
+}
+
+impl<DB: Database> Inspector<DB> for StructLogInspector {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        let opcode = interp.current_opcode();
+        let memory = memory::to_words(interp.shared_memory.context_memory());
+        self.gas_before_step = interp.gas.remaining();
+        self.steps.push(json!({
+            "pc": interp.program_counter(),
+            "op": OpCode::new(opcode)
+                .map_or(format!("opcode 0x{:x}", opcode), |op| op.as_str().to_string()),
+            "gas": self.gas_before_step,
+            "gasCost": 0,
+            "depth": context.journaled_state.depth(),
+            "stack": interp.stack.data(),
+            "memory": memory,
+        }));
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        if let Some(step) = self.steps.last_mut() {
+            step["gasCost"] = json!(self.gas_before_step.saturating_sub(interp.gas.remaining()));
+        }
+    }
+}
+
+impl StructLogInspector {
+    pub fn into_struct_logs(self) -> Result<Vec<StructLog>, eyre::Error> {
+        Ok(serde_json::from_value(serde_json::Value::Array(self.steps))?)
+    }
+}
+
+/// Runs a future to completion from the synchronous `Database` interface of the EVM
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(future))
+}
+
+/// Reads accounts and storage at a fixed block with the standard `eth_` methods
+pub struct RpcDatabase<'a> {
+    rpc: &'a RpcConfig,
+    provider: &'a RootProvider<BoxTransport>,
+    block: BlockId,
+}
+
+impl<'a> Database for RpcDatabase<'a> {
+    type Error = eyre::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let (rpc, provider, block) = (self.rpc, self.provider, self.block);
+        let (balance, nonce, code) = block_on(async {
+            tokio::try_join!(
+                rpc.request("eth_getBalance", || provider.get_balance(address, block)),
+                rpc.request("eth_getTransactionCount", || {
+                    provider.get_transaction_count(address, block)
+                }),
+                rpc.request("eth_getCode", || provider.get_code_at(address, block)),
+            )
+        })?;
+        let code = Bytecode::new_raw(code);
+        Ok(Some(AccountInfo::new(balance, nonce, code.hash_slow(), code)))
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        // `basic` always returns the code along with the account, so this is never needed
+        Err(eyre!("Code {} was not loaded with its account", code_hash))
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        let (provider, block) = (self.provider, self.block);
+        block_on(
+            self.rpc
+                .request("eth_getStorageAt", || provider.get_storage_at(address, index, block)),
+        )
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        let provider = self.provider;
+        let block = block_on(self.rpc.request("eth_getBlockByNumber", || {
+            provider.get_block_by_number(BlockNumberOrTag::Number(number.to::<u64>()), false)
+        }))?;
+        block
+            .and_then(|block| block.header.hash)
+            .ok_or_else(|| eyre!("Block {} does not exist", number))
+    }
+}
+
+/// Re-executes a mined transaction in an embedded EVM on top of the state before its block,
+/// including the transactions that precede it in the same block. The hardfork active at the
+/// block is used unless one is given
+pub async fn replay_transaction(
+    rpc: &RpcConfig,
+    provider: &RootProvider<BoxTransport>,
+    transaction: TxHash,
+    hardfork: Option<Hardfork>,
+) -> Result<TraceData, eyre::Error> {
+    let transaction_result = rpc
+        .request("eth_getTransactionByHash", || provider.get_transaction_by_hash(transaction))
+        .await?;
+    let block_number = transaction_result
+        .block_number
+        .ok_or_else(|| eyre!("Transaction {} is not mined yet", transaction))?;
+    let block = rpc
+        .request("eth_getBlockByNumber", || {
+            provider.get_block_by_number(BlockNumberOrTag::Number(block_number), true)
+        })
+        .await?
+        .ok_or_else(|| eyre!("Block {} does not exist", block_number))?;
+    let chain_id = rpc.request("eth_chainId", || provider.get_chain_id()).await?;
+    let spec_id = match hardfork {
+        Some(hardfork) => hardfork.spec_id(),
+        None => spec_id(chain_id, block_number, block.header.timestamp)?,
+    };
+
+    let BlockTransactions::Full(block_transactions) = &block.transactions else {
+        return Err(eyre!("Block {} was returned without its transactions", block_number));
+    };
+
+    let mut db = CacheDB::new(RpcDatabase {
+        rpc,
+        provider,
+        block: BlockId::Number(BlockNumberOrTag::Number(block_number.saturating_sub(1))),
+    });
+
+    for previous in block_transactions.iter().take_while(|previous| previous.hash != transaction) {
+        let mut evm = Evm::builder()
+            .with_db(&mut db)
+            .with_external_context(NoOpInspector)
+            .with_spec_id(spec_id)
+            .modify_cfg_env(|cfg| cfg.chain_id = chain_id)
+            .modify_block_env(|block_env| fill_block_env(block_env, &block.header))
+            .modify_tx_env(|tx_env| fill_tx_env(tx_env, previous))
+            .build();
+        evm.transact_commit()
+            .map_err(|error| eyre!("Failed to replay {}: {:?}", previous.hash, error))?;
+    }
+
+    let mut evm = Evm::builder()
+        .with_db(&mut db)
+        .with_external_context(StructLogInspector::default())
+        .append_handler_register(inspector_handle_register)
+        .with_spec_id(spec_id)
+        .modify_cfg_env(|cfg| cfg.chain_id = chain_id)
+        .modify_block_env(|block_env| fill_block_env(block_env, &block.header))
+        .modify_tx_env(|tx_env| fill_tx_env(tx_env, &transaction_result))
+        .build();
+    let result = evm
+        .transact()
+        .map_err(|error| eyre!("Failed to replay {}: {:?}", transaction, error))?;
+    let inspector = std::mem::take(&mut evm.context.external);
+
+    Ok(TraceData {
+        transaction: transaction_result,
+        success: result.result.is_success(),
+        struct_logs: inspector.into_struct_logs()?,
+    })
+}
+
+/// Executes raw runtime bytecode with the given calldata in an otherwise empty or seeded state,
+/// with the latest supported hardfork unless one is given
+pub fn run_bytecode(
+    code: Bytes,
+    calldata: Bytes,
+    value: U256,
+    caller: Address,
+    state: HashMap<Address, SeedAccount>,
+    hardfork: Option<Hardfork>,
+) -> Result<TraceData, eyre::Error> {
+    let mut db = CacheDB::new(EmptyDB::default());
+
+    for (address, account) in state {
+        let code = Bytecode::new_raw(account.code);
+        db.insert_account_info(
+            address,
+            AccountInfo::new(account.balance, account.nonce, code.hash_slow(), code),
+        );
+        for (slot, slot_value) in account.storage {
+            db.insert_account_storage(address, slot, slot_value)?;
+        }
+    }
+
+    let code = Bytecode::new_raw(code);
+    let mut target = db.basic(PLAYGROUND_ADDRESS)?.unwrap_or_default();
+    target.code_hash = code.hash_slow();
+    target.code = Some(code);
+    db.insert_account_info(PLAYGROUND_ADDRESS, target);
+
+    // Make sure the caller can afford the value it sends
+    let mut sender = db.basic(caller)?.unwrap_or_default();
+    sender.balance = sender.balance.max(value);
+    db.insert_account_info(caller, sender);
+
+    let mut evm = Evm::builder()
+        .with_db(&mut db)
+        .with_external_context(StructLogInspector::default())
+        .append_handler_register(inspector_handle_register)
+        .with_spec_id(hardfork.map_or(SpecId::CANCUN, Hardfork::spec_id))
+        .modify_tx_env(|tx_env| {
+            tx_env.caller = caller;
+            tx_env.gas_limit = PLAYGROUND_GAS_LIMIT;
+            tx_env.gas_price = U256::ZERO;
+            tx_env.transact_to = TransactTo::Call(PLAYGROUND_ADDRESS);
+            tx_env.value = value;
+            tx_env.data = calldata.clone();
+        })
+        .build();
+    let result = evm.transact().map_err(|error| eyre!("Failed to run the bytecode: {:?}", error))?;
+    let inspector = std::mem::take(&mut evm.context.external);
+
+    let transaction = Transaction {
+        from: caller,
+        to: Some(PLAYGROUND_ADDRESS),
+        value,
+        input: calldata,
+        gas: PLAYGROUND_GAS_LIMIT as u128,
+        ..Default::default()
+    };
+
+    Ok(TraceData {
+        transaction,
+        success: result.result.is_success(),
+        struct_logs: inspector.into_struct_logs()?,
+    })
+}
+
+pub fn fill_block_env(block_env: &mut BlockEnv, header: &Header) {
+    block_env.number = U256::from(header.number.unwrap_or_default());
+    block_env.coinbase = header.miner;
+    block_env.timestamp = U256::from(header.timestamp);
+    block_env.gas_limit = U256::from(header.gas_limit);
+    block_env.basefee = U256::from(header.base_fee_per_gas.unwrap_or_default());
+    block_env.difficulty = header.difficulty;
+    block_env.prevrandao = header.mix_hash;
+    if let Some(excess_blob_gas) = header.excess_blob_gas {
+        block_env.set_blob_excess_gas_and_price(excess_blob_gas as u64);
+    }
+}
+
+pub fn fill_tx_env(tx_env: &mut TxEnv, transaction: &Transaction) {
+    tx_env.caller = transaction.from;
+    tx_env.gas_limit = transaction.gas as u64;
+    // EIP-1559 transactions report their effective price in `gas_price`, revm wants the cap
+    tx_env.gas_price =
+        U256::from(transaction.max_fee_per_gas.or(transaction.gas_price).unwrap_or_default());
+    tx_env.gas_priority_fee = transaction.max_priority_fee_per_gas.map(U256::from);
+    tx_env.transact_to = match transaction.to {
+        Some(to) => TransactTo::Call(to),
+        None => TransactTo::create(),
+    };
+    tx_env.value = transaction.value;
+    tx_env.data = transaction.input.clone();
+    tx_env.nonce = Some(transaction.nonce);
+    tx_env.chain_id = transaction.chain_id;
+    tx_env.access_list = transaction
+        .access_list
+        .as_ref()
+        .map(|access_list| {
+            access_list
+                .0
+                .iter()
+                .map(|item| {
+                    (
+                        item.address,
+                        item.storage_keys.iter().map(|key| U256::from_be_bytes(key.0)).collect(),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    tx_env.blob_hashes = transaction.blob_versioned_hashes.clone().unwrap_or_default();
+    tx_env.max_fee_per_blob_gas = transaction.max_fee_per_blob_gas.map(U256::from);
+}
+
+/// Hardfork active at a block of mainnet, Sepolia or Holesky. Hardforks since Shanghai are
+/// scheduled by timestamp, the older ones by block number. Cancun is the latest hardfork the
+/// embedded EVM runs, so blocks from Prague on are refused rather than replayed with the wrong
+/// rules
+fn spec_id(chain_id: u64, block_number: u64, timestamp: u64) -> Result<SpecId, eyre::Error> {
+    let (merge, shanghai, cancun, prague) = match chain_id {
+        1 => (15_537_394, 1_681_338_455, 1_710_338_135, 1_746_612_311),
+        11_155_111 => (1_735_371, 1_677_557_088, 1_706_655_072, 1_741_159_776),
+        17_000 => (0, 1_696_000_704, 1_707_305_664, 1_740_434_112),
+        _ => {
+            return Err(eyre!(
+                "The hardforks of chain {} are not known, pass --hardfork to pick one",
+                chain_id
+            ))
+        }
+    };
+    if timestamp >= prague {
+        return Err(eyre!(
+            "Block {} of chain {} runs Prague or later, which the replay tracer does not know, \
+             pass --hardfork to replay it with older rules anyway",
+            block_number,
+            chain_id
+        ));
+    }
+    Ok(match (block_number, timestamp) {
+        (_, timestamp) if timestamp >= cancun => SpecId::CANCUN,
+        (_, timestamp) if timestamp >= shanghai => SpecId::SHANGHAI,
+        (block_number, _) if block_number >= merge => SpecId::MERGE,
+        // Sepolia started out on London
+        _ if chain_id != 1 => SpecId::LONDON,
+        (0..=1_149_999, _) => SpecId::FRONTIER,
+        (1_150_000..=2_462_999, _) => SpecId::HOMESTEAD,
+        (2_463_000..=2_674_999, _) => SpecId::TANGERINE,
+        (2_675_000..=4_369_999, _) => SpecId::SPURIOUS_DRAGON,
+        (4_370_000..=7_279_999, _) => SpecId::BYZANTIUM,
+        (7_280_000..=9_068_999, _) => SpecId::PETERSBURG,
+        (9_069_000..=12_243_999, _) => SpecId::ISTANBUL,
+        (12_244_000..=12_964_999, _) => SpecId::BERLIN,
+        _ => SpecId::LONDON,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_nonces_are_numbers_or_hex_strings() {
+        let state: HashMap<Address, SeedAccount> = serde_json::from_value(json!({
+            "0x0000000000000000000000000000000000000001": { "nonce": 7 },
+            "0x0000000000000000000000000000000000000002": { "nonce": "0x1f" },
+            "0x0000000000000000000000000000000000000003": { "balance": "0x10" }
+        }))
+        .unwrap();
+        let nonce = |byte: u8| state[&Address::with_last_byte(byte)].nonce;
+        assert_eq!((nonce(1), nonce(2), nonce(3)), (7, 31, 0));
+    }
+
+    #[test]
+    fn blocks_past_the_known_hardforks_are_refused() {
+        assert_eq!(spec_id(1, 19_426_587, 1_710_338_135).unwrap(), SpecId::CANCUN);
+        assert_eq!(spec_id(1, 12_965_000, 1_628_166_822).unwrap(), SpecId::LONDON);
+        assert!(spec_id(1, 22_431_084, 1_746_612_311).is_err());
+        assert!(spec_id(17_000, 3_419_704, 1_740_434_112).is_err());
+        assert!(spec_id(10, 1, 1).is_err());
+    }
+}
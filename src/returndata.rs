@@ -0,0 +1,189 @@
+use std::ops::Range;
+
+use alloy::{primitives::Bytes, rpc::types::trace::geth::StructLog};
+
+use crate::{
+    access::{arg_usize, call_args},
+    calltree::{memory_bytes, CallTree},
+};
+
+/// Return data a sub call left in the buffer of its caller
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnBuffer {
+    /// Call tree node of the sub call
+    pub call: usize,
+    /// Call tree node of the caller owning the buffer
+    pub frame: usize,
+    /// First operation of the caller after the sub call returned
+    pub operation: usize,
+    /// Contents of the buffer, `None` if the trace does not tell
+    pub data: Option<Bytes>,
+    /// Size of the buffer, also known from RETURNDATASIZE when the contents are not
+    pub size: Option<usize>,
+    /// Ranges of the buffer copied into memory with the operation copying them, the output
+    /// range of the call itself first
+    pub copies: Vec<(usize, Range<usize>)>,
+}
+
+impl ReturnBuffer {
+    /// Number of distinct bytes of the buffer copied into memory up to the given operation
+    pub fn copied(&self, operation: usize) -> usize {
+        let size = self.size.unwrap_or(usize::MAX);
+        let mut ranges: Vec<Range<usize>> = self
+            .copies
+            .iter()
+            .filter(|(copy, _)| *copy <= operation)
+            .map(|(_, range)| range.start.min(size)..range.end.min(size))
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        let (mut copied, mut end) = (0, 0);
+        for range in ranges {
+            copied += range.end.saturating_sub(range.start.max(end));
+            end = end.max(range.end);
+        }
+        copied
+    }
+
+    /// Whether the given byte of the buffer was copied into memory up to the given operation
+    pub fn is_copied(&self, operation: usize, byte: usize) -> bool {
+        self.copies.iter().any(|(copy, range)| *copy <= operation && range.contains(&byte))
+    }
+}
+
+/// The return data buffers of every call frame of a transaction
+#[derive(Debug, Clone, Default)]
+pub struct ReturnData {
+    /// Buffers in the order the sub calls were made
+    pub buffers: Vec<ReturnBuffer>,
+}
+
+impl ReturnData {
+    pub fn build(steps: &[StructLog], call_tree: &CallTree) -> Self {
+        let mut buffers = vec![];
+        for (call, node) in call_tree.nodes.iter().enumerate() {
+            let Some(frame) = node.parent else {
+                continue;
+            };
+            // Calls without steps of their own start at the calling operation
+            let entered = steps.get(node.first_step).is_some_and(|step| step.depth == node.depth);
+            let (calling, operation) = match entered {
+                true => (node.first_step.saturating_sub(1), node.last_step + 1),
+                false => (node.first_step, node.first_step + 1),
+            };
+            let Some(resumed) = steps.get(operation) else {
+                continue;
+            };
+
+            // Without return data in the trace, the output of the callee is read from the
+            // memory it returned
+            let data = resumed.return_data.clone().or_else(|| {
+                let last = steps.get(node.last_step).filter(|_| entered)?;
+                let stack = last.stack.as_deref().unwrap_or_default();
+                let memory = last.memory.as_deref().unwrap_or_default();
+                match last.op.as_str() {
+                    // A successful creation leaves the buffer empty
+                    "RETURN" if node.kind.is_create() => Some(Bytes::new()),
+                    "RETURN" | "REVERT" => {
+                        let (offset, size) = (arg_usize(stack, 0), arg_usize(stack, 1));
+                        Some(Bytes::from(memory_bytes(memory, offset, size)))
+                    }
+                    _ => Some(Bytes::new()),
+                }
+            });
+            let mut copies = vec![];
+            let caller = &steps[calling];
+            if !node.kind.is_create() {
+                let stack = caller.stack.as_deref().unwrap_or_default();
+                copies.push((operation, 0..arg_usize(stack, call_args(&caller.op) + 3)));
+            }
+            buffers.push(ReturnBuffer {
+                call,
+                frame,
+                operation,
+                size: data.as_ref().map(Bytes::len),
+                data,
+                copies,
+            });
+        }
+
+        // Operations of a frame use the buffer of its latest sub call
+        for (index, step) in steps.iter().enumerate() {
+            if !matches!(step.op.as_str(), "RETURNDATACOPY" | "RETURNDATASIZE") {
+                continue;
+            }
+            let frame = call_tree.node_at(index, step.depth);
+            let Some(buffer) = buffers
+                .iter_mut()
+                .filter(|buffer| buffer.frame == frame && buffer.operation <= index)
+                .last()
+            else {
+                continue;
+            };
+            let stack = step.stack.as_deref().unwrap_or_default();
+            match step.op.as_str() {
+                "RETURNDATACOPY" => {
+                    let (offset, size) = (arg_usize(stack, 1), arg_usize(stack, 2));
+                    buffer.copies.push((index, offset..offset.saturating_add(size)));
+                }
+                _ => {
+                    let next = steps.get(index + 1).and_then(|next| next.stack.as_deref());
+                    buffer.size = buffer.size.or(next.map(|stack| arg_usize(stack, 0)));
+                }
+            }
+        }
+
+        Self { buffers }
+    }
+
+    /// Buffer of the frame running the given operation, `None` before its first sub call
+    pub fn buffer_at(
+        &self,
+        call_tree: &CallTree,
+        steps: &[StructLog],
+        operation: usize,
+    ) -> Option<&ReturnBuffer> {
+        let frame = call_tree.node_at(operation, steps.get(operation)?.depth);
+        self.buffers
+            .iter()
+            .filter(|buffer| buffer.frame == frame && buffer.operation <= operation)
+            .last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::rpc::types::eth::Transaction;
+
+    use super::*;
+    use crate::test_utils::{step, struct_logs, with_memory};
+
+    #[test]
+    fn copies_are_tracked_per_buffer() {
+        let steps = struct_logs(vec![
+            step(1, "CALL", &[0, 0, 0, 0, 0, 0xaa, 1000]),
+            with_memory(step(2, "RETURN", &[64, 0]), 2),
+            step(1, "RETURNDATACOPY", &[32, 0, 0]),
+            with_memory(step(1, "STOP", &[]), 1),
+        ]);
+        let call_tree = CallTree::build(&Transaction::default(), true, &steps);
+        let return_data = ReturnData::build(&steps, &call_tree);
+        assert!(return_data.buffer_at(&call_tree, &steps, 0).is_none());
+        let buffer = return_data.buffer_at(&call_tree, &steps, 3).unwrap();
+        assert_eq!(buffer.size, Some(64));
+        // Half of the returned data never makes it into memory
+        assert_eq!(buffer.copied(3), 32);
+        assert!(buffer.is_copied(3, 31) && !buffer.is_copied(3, 32));
+    }
+
+    #[test]
+    fn copies_reaching_past_the_address_space_are_clamped() {
+        let steps = struct_logs(vec![
+            step(1, "CALL", &[0, 0, 0, 0, 0, 0xaa, 1000]),
+            step(2, "STOP", &[]),
+            step(1, "RETURNDATACOPY", &[u64::MAX, u64::MAX, 0]),
+        ]);
+        let call_tree = CallTree::build(&Transaction::default(), true, &steps);
+        let return_data = ReturnData::build(&steps, &call_tree);
+        assert_eq!(return_data.buffers[0].copied(2), 0);
+    }
+}
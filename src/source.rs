@@ -0,0 +1,348 @@
+use std::{collections::HashMap, io::Read, path::PathBuf, str::FromStr};
+
+use alloy::{
+    primitives::{Address, Bytes, TxHash, U256},
+    providers::{Provider, RootProvider},
+    rpc::types::{
+        eth::{BlockId, BlockNumberOrTag, Transaction, TransactionRequest},
+        trace::{
+            self,
+            geth::{
+                GethDebugTracingCallOptions, GethDebugTracingOptions, GethDefaultTracingOptions,
+                GethTrace, StructLog,
+            },
+        },
+    },
+    transports::BoxTransport,
+};
+use clap::ValueEnum;
+use color_eyre::eyre::{self, eyre};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    cache::{self, CacheKey, CacheMode},
+    memory::MemoryReconstructor,
+    parity,
+    provider::{self, RpcConfig},
+    replay::{self, Hardfork},
+};
+
+/// Path that makes a file input read from stdin instead
+static STDIN_PATH: &str = "-";
+
+/// Where the struct logs of a transaction come from
+#[derive(Debug, Clone)]
+pub enum TraceSource {
+    /// Trace a mined transaction with the given backend
+    Rpc {
+        rpc: RpcConfig,
+        transaction: TxHash,
+        cache: CacheMode,
+        backend: TraceBackend,
+        /// Fetch the struct logs without memory and rebuild it locally
+        reconstruct_memory: bool,
+        /// Hardfork the replay backend executes with instead of the one active at the block
+        hardfork: Option<Hardfork>,
+    },
+    /// Load a saved `debug_traceTransaction` response and, optionally, the transaction JSON
+    File { trace: PathBuf, transaction: Option<PathBuf> },
+    /// Trace a call that was never mined with `debug_traceCall`
+    Call(CallRequest),
+    /// Execute raw runtime bytecode in an embedded EVM
+    Bytecode(BytecodeRequest),
+    /// A trace that was already fetched, e.g. together with the rest of its block
+    Loaded(Box<TraceData>),
+}
+
+/// How the struct logs of a mined transaction are produced
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum TraceBackend {
+    /// Ask the node with `debug_traceTransaction`
+    #[default]
+    Debug,
+    /// Re-execute the transaction locally against state fetched with standard `eth_` calls
+    Replay,
+    /// Convert the vmTrace of `trace_replayTransaction` served by Erigon, Nethermind and reth
+    Parity,
+}
+
+/// A call to execute with `debug_traceCall`
+#[derive(Debug, Clone)]
+pub struct CallRequest {
+    pub rpc: RpcConfig,
+    pub from: Option<Address>,
+    pub to: Address,
+    pub data: Bytes,
+    pub value: U256,
+    pub block: BlockNumberOrTag,
+    /// JSON file with state overrides keyed by account address
+    pub state_overrides: Option<PathBuf>,
+    /// JSON file with block overrides
+    pub block_overrides: Option<PathBuf>,
+    /// Fetch the struct logs without memory and rebuild it locally
+    pub reconstruct_memory: bool,
+}
+
+/// Everything a `TransactionState` needs from a trace source
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TraceData {
+    /// Transaction details
+    pub transaction: Transaction,
+    /// Success of the transaction
+    pub success: bool,
+    /// The struct logs of the trace
+    pub struct_logs: Vec<StructLog>,
+}
+
+/// Raw runtime bytecode to execute without a node
+#[derive(Debug, Clone)]
+pub struct BytecodeRequest {
+    /// Hex encoded bytecode, or a file containing it
+    pub code: String,
+    pub calldata: Bytes,
+    pub value: U256,
+    pub caller: Address,
+    /// JSON file with the accounts to seed the state with, in the genesis `alloc` format
+    pub state: Option<PathBuf>,
+    /// Hardfork to execute with instead of the latest one
+    pub hardfork: Option<Hardfork>,
+}
+
+impl TraceSource {
+    /// Fetches or reads the trace described by the source
+    pub async fn fetch(&self) -> Result<TraceData, eyre::Error> {
+        match self {
+            TraceSource::Rpc { rpc, transaction, cache, backend, reconstruct_memory, hardfork } => {
+                fetch_rpc(rpc, *transaction, *cache, *backend, *reconstruct_memory, *hardfork).await
+            }
+            TraceSource::File { trace, transaction } => read_files(trace, transaction.as_ref()),
+            TraceSource::Call(request) => fetch_call(request).await,
+            TraceSource::Bytecode(request) => run_bytecode(request),
+            TraceSource::Loaded(data) => Ok(data.as_ref().clone()),
+        }
+    }
+}
+
+/// Tracing options for the default struct logger, with the node-side tracer timeout raised to
+/// the request timeout when there is one
+pub fn tracing_options(rpc: &RpcConfig, enable_memory: bool) -> GethDebugTracingOptions {
+    GethDebugTracingOptions {
+        config: GethDefaultTracingOptions {
+            enable_memory: Some(enable_memory),
+            disable_memory: None,
+            disable_stack: Some(false),
+            disable_storage: Some(true),
+            enable_return_data: Some(true),
+            disable_return_data: Some(false),
+            debug: None,
+            limit: None,
+        },
+        tracer: None,
+        tracer_config: trace::geth::GethDebugTracerConfig(serde_json::Value::Null),
+        timeout: rpc.timeout.map(|timeout| format!("{}s", timeout.as_secs())),
+    }
+}
+
+async fn fetch_rpc(
+    rpc: &RpcConfig,
+    transaction: TxHash,
+    cache_mode: CacheMode,
+    backend: TraceBackend,
+    reconstruct_memory: bool,
+    hardfork: Option<Hardfork>,
+) -> Result<TraceData, eyre::Error> {
+    let provider = provider::RpcProvider::init(rpc).await?;
+    let chain_id = rpc.request("eth_chainId", || provider.get_chain_id()).await?;
+    // Only the replay tracer runs the EVM itself, the other backends ignore the hardfork
+    let hardfork = hardfork.filter(|_| backend == TraceBackend::Replay);
+    let key = CacheKey { chain_id, transaction, backend, reconstruct_memory, hardfork };
+
+    if cache_mode == CacheMode::Enabled {
+        if let Some(data) = cache::load(&key) {
+            return Ok(data);
+        }
+    }
+
+    let data = match backend {
+        TraceBackend::Debug => {
+            debug_trace(rpc, &provider, transaction, reconstruct_memory).await?
+        }
+        TraceBackend::Replay => {
+            replay::replay_transaction(rpc, &provider, transaction, hardfork).await?
+        }
+        TraceBackend::Parity => parity::replay_transaction(rpc, &provider, transaction).await?,
+    };
+
+    if cache_mode != CacheMode::Disabled {
+        if let Err(error) = cache::store(&key, &data) {
+            tracing::warn!("Failed to cache the trace of {}: {}", transaction, error);
+        }
+    }
+
+    Ok(data)
+}
+
+async fn debug_trace(
+    rpc: &RpcConfig,
+    provider: &RootProvider<BoxTransport>,
+    transaction: TxHash,
+    reconstruct_memory: bool,
+) -> Result<TraceData, eyre::Error> {
+    let transaction_result = rpc
+        .request("eth_getTransactionByHash", || provider.get_transaction_by_hash(transaction))
+        .await?;
+    let options = tracing_options(rpc, !reconstruct_memory);
+    let result = rpc
+        .request("debug_traceTransaction", || {
+            provider.debug_trace_transaction(transaction, options.clone())
+        })
+        .await?;
+
+    match result {
+        GethTrace::JS(context) => {
+            let (success, mut struct_logs) = parse_trace(context)?;
+            if reconstruct_memory {
+                // Code is read from the state the transaction started from
+                let block = transaction_result.block_number.map_or(BlockId::latest(), |number| {
+                    BlockId::number(number.saturating_sub(1))
+                });
+                MemoryReconstructor::new(rpc, provider, block)
+                    .reconstruct(&mut struct_logs, &transaction_result)
+                    .await?;
+            }
+            Ok(TraceData { transaction: transaction_result, success, struct_logs })
+        }
+        _ => Err(eyre!("Unexpected trace format returned for {}", transaction)),
+    }
+}
+
+async fn fetch_call(request: &CallRequest) -> Result<TraceData, eyre::Error> {
+    let provider = provider::RpcProvider::init(&request.rpc).await?;
+
+    let mut transaction_request = TransactionRequest::default()
+        .to(request.to)
+        .value(request.value)
+        .input(request.data.clone().into());
+    if let Some(from) = request.from {
+        transaction_request = transaction_request.from(from);
+    }
+
+    let opts = GethDebugTracingCallOptions {
+        tracing_options: tracing_options(&request.rpc, !request.reconstruct_memory),
+        state_overrides: request
+            .state_overrides
+            .as_ref()
+            .map(|path| read_json(path).and_then(|value| Ok(serde_json::from_value(value)?)))
+            .transpose()?,
+        block_overrides: request
+            .block_overrides
+            .as_ref()
+            .map(|path| read_json(path).and_then(|value| Ok(serde_json::from_value(value)?)))
+            .transpose()?,
+    };
+
+    let result = request
+        .rpc
+        .request("debug_traceCall", || {
+            provider.debug_trace_call(transaction_request.clone(), request.block, opts.clone())
+        })
+        .await?;
+
+    match result {
+        GethTrace::JS(context) => {
+            let (success, mut struct_logs) = parse_trace(context)?;
+            // The call was never mined, so only the fields of the request are known
+            let transaction = Transaction {
+                from: request.from.unwrap_or_default(),
+                to: Some(request.to),
+                value: request.value,
+                input: request.data.clone(),
+                ..Default::default()
+            };
+            if request.reconstruct_memory {
+                // CODECOPY of an overridden account copies the code the call actually ran
+                let codes = opts.state_overrides.iter().flatten().filter_map(|(address, account)| {
+                    Some((*address, account.code.clone()?))
+                });
+                MemoryReconstructor::new(&request.rpc, &provider, BlockId::Number(request.block))
+                    .override_code(codes)
+                    .reconstruct(&mut struct_logs, &transaction)
+                    .await?;
+            }
+            Ok(TraceData { transaction, success, struct_logs })
+        }
+        _ => Err(eyre!("Unexpected trace format returned for the call to {}", request.to)),
+    }
+}
+
+fn run_bytecode(request: &BytecodeRequest) -> Result<TraceData, eyre::Error> {
+    let path = PathBuf::from(&request.code);
+    let code = if path.is_file() {
+        std::fs::read_to_string(&path)
+            .map_err(|error| eyre!("Failed to read {}: {}", path.display(), error))?
+    } else {
+        request.code.clone()
+    };
+    let code = Bytes::from_str(code.trim())
+        .map_err(|error| eyre!("The bytecode is neither a file nor valid hex: {}", error))?;
+    let state: HashMap<Address, replay::SeedAccount> = match &request.state {
+        Some(path) => serde_json::from_value(read_json(path)?)?,
+        None => HashMap::new(),
+    };
+
+    replay::run_bytecode(
+        code,
+        request.calldata.clone(),
+        request.value,
+        request.caller,
+        state,
+        request.hardfork,
+    )
+}
+
+fn read_files(trace: &PathBuf, transaction: Option<&PathBuf>) -> Result<TraceData, eyre::Error> {
+    if trace.as_os_str() == STDIN_PATH
+        && transaction.is_some_and(|path| path.as_os_str() == STDIN_PATH)
+    {
+        return Err(eyre!("The trace and the transaction cannot both be read from stdin"));
+    }
+
+    let (success, struct_logs) = parse_trace(read_json(trace)?)?;
+    let transaction = match transaction {
+        Some(path) => serde_json::from_value(unwrap_rpc_response(read_json(path)?))?,
+        None => Transaction::default(),
+    };
+
+    Ok(TraceData { transaction, success, struct_logs })
+}
+
+/// Reads a JSON document from a file, or from stdin if the path is `-`
+fn read_json(path: &PathBuf) -> Result<serde_json::Value, eyre::Error> {
+    let content = if path.as_os_str() == STDIN_PATH {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|error| eyre!("Failed to read {}: {}", path.display(), error))?
+    };
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Accepts both a bare result object and a full JSON-RPC response wrapping it
+fn unwrap_rpc_response(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(mut object) if object.contains_key("result") => {
+            object.remove("result").unwrap_or_default()
+        }
+        value => value,
+    }
+}
+
+/// Extracts the failed flag and the struct logs from a `debug_traceTransaction` result
+pub fn parse_trace(context: serde_json::Value) -> Result<(bool, Vec<StructLog>), eyre::Error> {
+    let context = unwrap_rpc_response(context);
+    let failed: bool = serde_json::from_value(context["failed"].clone())?;
+    let struct_logs = serde_json::from_value(context["structLogs"].clone())?;
+    Ok((!failed, struct_logs))
+}
@@ -0,0 +1,128 @@
+use alloy::primitives::U256;
+
+use crate::{opcodes, state::TransactionState};
+
+/// How a stack item changed since the previous operation of its frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackChange {
+    Kept,
+    Pushed,
+    /// No longer on the stack, listed above the top
+    Popped,
+}
+
+/// An item of the stack as the stack panel lists it
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackItem {
+    /// Distance from the top of the stack, `None` for popped items
+    pub depth: Option<usize>,
+    pub value: U256,
+    /// Name of the operand the operation consumes this item as
+    pub operand: Option<&'static str>,
+    pub change: StackChange,
+}
+
+/// Stack of the frame before the given operation ran in it, following calls back to the
+/// operation that made them
+fn previous_stack(transaction_state: &TransactionState, operation: usize) -> Option<&[U256]> {
+    let steps = &transaction_state.raw_data;
+    let depth = steps.get(operation)?.depth;
+    let mut previous = operation.checked_sub(1)?;
+    if steps[previous].depth > depth {
+        // The frame resumes after a call, whose node starts right after the calling operation
+        let call_tree = &transaction_state.call_tree;
+        let node = call_tree.nodes.get(call_tree.node_at(previous, depth + 1))?;
+        previous = node.first_step.checked_sub(1)?;
+    }
+    match steps.get(previous) {
+        Some(step) if step.depth == depth => step.stack.as_deref(),
+        // The frame was entered at this operation and started with an empty stack
+        _ => None,
+    }
+}
+
+/// Every item of the stack the given operation sees, top first, with the items popped since
+/// the previous operation of the frame listed above them
+pub fn inspect(transaction_state: &TransactionState, operation: usize) -> Vec<StackItem> {
+    let Some(step) = transaction_state.raw_data.get(operation) else {
+        return vec![];
+    };
+    let stack = step.stack.as_deref().unwrap_or_default();
+    let previous = previous_stack(transaction_state, operation).unwrap_or_default();
+    // Items below the first difference from the previous stack were left untouched
+    let kept = stack.iter().zip(previous).take_while(|(item, before)| item == before).count();
+    let operands = opcodes::operands(&step.op);
+
+    let popped = previous[kept..].iter().rev().map(|value| StackItem {
+        depth: None,
+        value: *value,
+        operand: None,
+        change: StackChange::Popped,
+    });
+    let items = stack.iter().rev().enumerate().map(|(depth, value)| StackItem {
+        depth: Some(depth),
+        value: *value,
+        operand: operands.get(depth).copied().filter(|operand| !operand.is_empty()),
+        change: match depth < stack.len() - kept {
+            true => StackChange::Pushed,
+            false => StackChange::Kept,
+        },
+    });
+    popped.chain(items).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        calltree::CallTree,
+        test_utils::{step, struct_logs},
+    };
+
+    /// A transaction whose steps run at the given depths with the given stacks, top last
+    fn transaction_state(steps: &[(u64, &str, &[u64])]) -> TransactionState {
+        let raw_data =
+            struct_logs(steps.iter().map(|(depth, op, stack)| step(*depth, op, stack)).collect());
+        let call_tree = CallTree::build(&Default::default(), true, &raw_data);
+        TransactionState { raw_data, call_tree, ..Default::default() }
+    }
+
+    #[test]
+    fn items_are_diffed_against_the_previous_step_of_the_frame() {
+        let state = transaction_state(&[
+            (1, "PUSH1", &[1, 2]),
+            (1, "CALL", &[1, 2, 3]),
+            (2, "STOP", &[]),
+            (1, "ADD", &[1, 2, 1, 4]),
+            (1, "STOP", &[1, 2, 5]),
+        ]);
+        let changes = |operation| {
+            inspect(&state, operation)
+                .into_iter()
+                .map(|item| (item.depth, item.change))
+                .collect::<Vec<_>>()
+        };
+        // The caller resumes where it left before the call
+        assert_eq!(
+            changes(3),
+            vec![
+                (None, StackChange::Popped),
+                (Some(0), StackChange::Pushed),
+                (Some(1), StackChange::Pushed),
+                (Some(2), StackChange::Kept),
+                (Some(3), StackChange::Kept),
+            ]
+        );
+        assert_eq!(inspect(&state, 3)[1].operand, Some("a"));
+        assert_eq!(
+            changes(4)[..3],
+            [
+                (None, StackChange::Popped),
+                (None, StackChange::Popped),
+                (Some(0), StackChange::Pushed)
+            ]
+        );
+        // A frame starts with an empty stack
+        assert!(inspect(&state, 2).is_empty());
+    }
+}
@@ -1,20 +1,28 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use alloy::{
-    primitives::{TxHash, Uint, U256},
-    providers::Provider,
-    rpc::types::{
-        eth::Transaction,
-        trace::{
-            self,
-            geth::{GethDebugTracingOptions, GethDefaultTracingOptions, GethTrace, StructLog},
-        },
-    },
+    primitives::U256,
+    rpc::types::{eth::Transaction, trace::geth::StructLog},
 };
-use color_eyre::eyre;
+use color_eyre::eyre::{self, eyre};
+use itertools::Itertools;
 use opcode_parser::Operations;
 
-use crate::provider;
+use crate::{
+    access::{self, MemoryAccess},
+    breakpoint::Breakpoint,
+    calldata::Abi,
+    calltree::CallTree,
+    gas,
+    jump::Jump,
+    number::parse_number,
+    opcodes,
+    returndata::ReturnData,
+    source::{TraceData, TraceSource},
+    storage::{StorageKind, StorageLog},
+    timeline::{Timeline, CHECKPOINT_INTERVAL},
+    watchpoint::Watchpoint,
+};
 
 #[derive(Debug, Clone, Default)]
 pub struct AppState {
@@ -33,26 +41,41 @@ pub struct AppState {
     pub pause: bool,
     /// Position of the scroller in the history box
     pub history_vertical_scroll: u16,
+    /// Number of items scrolled past at the top of the stack panel
+    pub stack_vertical_scroll: u16,
+    /// Track the status of every byte in addition to every slot
+    pub byte_statuses: bool,
+    /// Call frames shown in the memory box
+    pub frame_view: FrameView,
+    /// Panel shown next to the memory box
+    pub panel: Option<Panel>,
+    /// Text typed in the command prompt, `None` when the prompt is closed
+    pub command: Option<String>,
+    /// Outcome of the last command or the breakpoint that fired, shown in the prompt line
+    pub message: Option<String>,
+    /// Conditions that pause playback
+    pub breakpoints: Vec<Breakpoint>,
+    /// Memory words that pause playback when they are touched
+    pub watchpoints: Vec<Watchpoint>,
+    /// Functions calldata is decoded with
+    pub abi: Abi,
 }
 
 impl AppState {
     /// Initializes the state of its transactions and sets the mode of the app
-    pub async fn init(
-        &mut self,
-        rpc: &str,
-        transactions: Vec<TxHash>,
-    ) -> Result<&mut Self, eyre::Error> {
-        let mut first_transaction_state = TransactionState::default();
-        first_transaction_state.initialize(transactions[0], rpc).await.unwrap();
+    pub async fn init(&mut self, sources: Vec<TraceSource>) -> Result<&mut Self, eyre::Error> {
+        let mut transaction_states = vec![];
 
-        let mut transaction_states = vec![first_transaction_state];
+        for source in sources {
+            let mut transaction_state = TransactionState::default();
+            transaction_state.track_bytes = self.byte_statuses;
+            transaction_state.initialize(&source).await?;
+            transaction_states.push(transaction_state);
+        }
 
-        if transactions.len() > 1 {
+        if transaction_states.len() > 1 {
             // versus view
             self.mode = AppMode::Versus;
-            let mut second_transaction_state = TransactionState::default();
-            second_transaction_state.initialize(transactions[1], rpc).await.unwrap();
-            transaction_states.push(second_transaction_state);
         }
 
         self.transaction_states = transaction_states;
@@ -73,12 +96,164 @@ impl AppState {
             return Ok(self);
         }
 
-        for state in &mut self.transaction_states {
-            state.run(iteration, forward).await.unwrap();
+        // Every operation played forward is checked, so a breakpoint or watchpoint is never
+        // skipped when more than one operation runs per frame
+        let fired = match forward {
+            true => self
+                .transaction_states
+                .iter()
+                .enumerate()
+                .filter_map(|(index, state)| {
+                    let first = state.next_operation as usize;
+                    let last = (first as u64)
+                        .saturating_add(iteration)
+                        .min(state.raw_data.len() as u64);
+                    (first..last as usize).find_map(|operation| {
+                        self.stop_reason(state, operation).map(|reason| (operation, index, reason))
+                    })
+                })
+                .min_by_key(|(operation, ..)| *operation),
+            false => None,
+        };
+
+        match fired {
+            Some((operation, index, reason)) => {
+                // Transactions played side by side all stop at the operation that fired
+                for state in &mut self.transaction_states {
+                    state.seek(operation)?;
+                }
+                self.pause = true;
+                self.message = Some(match self.transaction_states.len() {
+                    1 => reason,
+                    _ => format!("Transaction {}: {}", index, reason),
+                });
+            }
+            None => {
+                for state in &mut self.transaction_states {
+                    state.run(iteration, forward).await?;
+                }
+            }
         }
 
         Ok(self)
     }
+
+    /// Describes the first breakpoint or watchpoint the operation meets
+    fn stop_reason(&self, state: &TransactionState, operation: usize) -> Option<String> {
+        let op = &state.raw_data[operation].op;
+        let breakpoint = self.breakpoints.iter().enumerate().find_map(|(index, breakpoint)| {
+            breakpoint.hit(state, operation, operation.checked_sub(1)).then(|| {
+                format!("Breakpoint {} ({}) hit at step {}", index, breakpoint, operation)
+            })
+        });
+        breakpoint.or_else(|| {
+            self.watchpoints.iter().enumerate().find_map(|(index, watchpoint)| {
+                let action = match watchpoint.hit(state, operation) {
+                    SlotStatus::Writing => "written",
+                    SlotStatus::Reading => "read",
+                    _ => return None,
+                };
+                Some(format!(
+                    "Watchpoint {} ({}) {} by {} at step {}",
+                    index, watchpoint, action, op, operation
+                ))
+            })
+        })
+    }
+
+    /// Runs a command typed in the prompt: a jump, `break <condition>` to add a breakpoint,
+    /// `break` to list them and `delete [<number>]` to remove one or all of them, and the same
+    /// with `watch` and `unwatch` for watchpoints. Returns the message to show
+    pub fn execute(&mut self, command: &str) -> Result<Option<String>, eyre::Error> {
+        let command = command.trim();
+        match command.split_once(' ').unwrap_or((command, "")) {
+            ("break", "") => Ok(Some(list("breakpoints", &self.breakpoints))),
+            ("break", condition) => {
+                self.breakpoints.push(condition.parse()?);
+                Ok(Some(format!("Breakpoint {} set", self.breakpoints.len() - 1)))
+            }
+            ("delete", "") => {
+                self.breakpoints.clear();
+                Ok(Some("Deleted every breakpoint".to_string()))
+            }
+            ("delete", index) => {
+                let index = parse_number(index)? as usize;
+                if index >= self.breakpoints.len() {
+                    return Err(eyre!("There is no breakpoint {}", index));
+                }
+                let breakpoint = self.breakpoints.remove(index);
+                Ok(Some(format!("Deleted breakpoint {} ({})", index, breakpoint)))
+            }
+            ("watch", "") => Ok(Some(list("watchpoints", &self.watchpoints))),
+            ("watch", words) => {
+                let watchpoint = self.watchpoint(words)?;
+                let message = format!("Watchpoint {} ({}) set", self.watchpoints.len(), watchpoint);
+                self.watchpoints.push(watchpoint);
+                Ok(Some(message))
+            }
+            ("unwatch", "") => {
+                self.watchpoints.clear();
+                Ok(Some("Deleted every watchpoint".to_string()))
+            }
+            ("unwatch", index) => {
+                let index = parse_number(index)? as usize;
+                if index >= self.watchpoints.len() {
+                    return Err(eyre!("There is no watchpoint {}", index));
+                }
+                let watchpoint = self.watchpoints.remove(index);
+                Ok(Some(format!("Deleted watchpoint {} ({})", index, watchpoint)))
+            }
+            _ => {
+                self.jump(&command.parse()?)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Parses the words of the `watch` command: `<words>` watches the current frame,
+    /// `<words> all` every frame, and `free [<count>]` the words the free memory pointer points
+    /// to
+    fn watchpoint(&self, words: &str) -> Result<Watchpoint, eyre::Error> {
+        let Some(state) = self.transaction_states.first() else {
+            return Err(eyre!("There is no transaction to watch"));
+        };
+        let mut watchpoint = match words.split_once(' ').unwrap_or((words, "")) {
+            ("free", "") => Watchpoint::free_memory(state, 1)?,
+            ("free", count) => Watchpoint::free_memory(state, parse_number(count)? as usize)?,
+            (words, "all") => return words.parse(),
+            _ => words.parse()?,
+        };
+        // Frames are told apart by their call tree node, which only means something for the
+        // transaction it was taken from
+        if matches!(self.mode, AppMode::Normal) {
+            let operation = state.next_operation.saturating_sub(1) as usize;
+            let depth = state.raw_data.get(operation).map_or(1, |step| step.depth);
+            watchpoint.frame = Some(state.call_tree.node_at(operation, depth));
+        }
+        Ok(watchpoint)
+    }
+
+    /// Shows the panel next to the memory box, or hides it if it is already shown
+    pub fn toggle_panel(&mut self, panel: Panel) {
+        self.panel = match self.panel == Some(panel) {
+            true => None,
+            false => Some(panel),
+        };
+    }
+
+    /// Seeks every transaction to the target of the jump, none of them moves if one of them
+    /// has no target
+    pub fn jump(&mut self, jump: &Jump) -> Result<&mut Self, eyre::Error> {
+        let targets = self
+            .transaction_states
+            .iter()
+            .map(|state| jump.target(state))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (state, target) in self.transaction_states.iter_mut().zip(targets) {
+            state.seek(target)?;
+        }
+        Ok(self)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -93,20 +268,57 @@ impl Default for AppMode {
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct TransactionState {
+/// Panels that can be shown next to the memory box, one at a time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Panel {
+    CallTree,
+    Storage,
+    TransientStorage,
+    Stack,
+    ReturnData,
+    Calldata,
+}
+
+/// Which call frames the memory box shows
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FrameView {
+    /// The frame executing the current operation
+    #[default]
+    Current,
+    /// A caller of the current frame, counted in frames above it
+    Caller(usize),
+    /// Every frame of the call stack side by side
+    All,
+}
+
+/// Memory of a single call frame, each frame starts with empty memory of its own
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameState {
+    /// Call depth of the frame, 1 for the transaction itself
+    pub depth: u64,
+    /// Last operation executed in the frame, its memory snapshot is the memory of the frame
+    pub last_operation: usize,
     /// Vector of slots with values of SlotStatus
     pub slots: Vec<SlotStatus>,
-    /// Slots that are not new and need to change their status in the next run
-    pub slot_indexes_to_change_status: Vec<i64>,
-    /// Number of indexed slots
-    pub indexed_slots_count: u64,
-    /// Next operation number to process
-    pub next_operation: u64,
+    /// Status of every byte of memory, only tracked when enabled
+    pub byte_statuses: Option<Vec<SlotStatus>>,
+    /// Memory ranges touched by the last operation of the frame, their slots change status the
+    /// next time the frame runs, which is after the callee returns for calls
+    pub pending_access: MemoryAccess,
     /// Next slots status
     pub next_slot_status: SlotStatus,
-    /// History of opcodes
-    pub operation_codes: Vec<Operations>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransactionState {
+    /// Memory states of the call stack, the innermost frame last
+    pub frames: Vec<FrameState>,
+    /// Track the status of every byte in addition to every slot
+    pub track_bytes: bool,
+    /// Next operation number to process
+    pub next_operation: u64,
+    /// History of opcodes, with the number of the operation they belong to
+    pub operation_codes: Vec<(usize, Operations)>,
     /// Raw returned data by the trace transaction call
     pub raw_data: Vec<StructLog>,
     /// Transaction details
@@ -119,6 +331,24 @@ pub struct TransactionState {
     pub read_dataset: Vec<(f64, f64)>,
     /// The write operations chart dataset
     pub write_dataset: Vec<(f64, f64)>,
+    /// Cumulative memory expansion gas chart dataset
+    pub expansion_dataset: Vec<(f64, f64)>,
+    /// Call frames of the transaction
+    pub call_tree: CallTree,
+    /// SLOADs and SSTOREs of the transaction
+    pub storage: StorageLog,
+    /// TLOADs and TSTOREs of the transaction
+    pub transient_storage: StorageLog,
+    /// Return data buffers the sub calls of the transaction left
+    pub return_data: ReturnData,
+    /// Call tree node under the cursor of the call tree panel
+    pub selected_call: usize,
+    /// Call tree nodes whose sub calls are hidden
+    pub collapsed_calls: Vec<usize>,
+    /// Call tree node the memory box and the history are limited to
+    pub scope: Option<usize>,
+    /// Checkpoints and chart points of the whole transaction, shared between clones
+    pub timeline: Arc<Timeline>,
 }
 
 #[derive(Debug, Clone)]
@@ -127,6 +357,8 @@ pub struct OperationData {
     pub params: HashMap<String, String>,
     pub remaining_gas: u64,
     pub gas_cost: u64,
+    /// Part of `gas_cost` paid for expanding memory
+    pub expansion_cost: u64,
     pub pc: u64,
     pub stack: Option<Vec<U256>>,
 }
@@ -138,12 +370,69 @@ impl Default for OperationData {
             params: HashMap::new(),
             remaining_gas: 0,
             gas_cost: 0,
+            expansion_cost: 0,
             pc: 0,
             stack: None,
         }
     }
 }
 
+impl OperationData {
+    pub fn from_step(step: &StructLog) -> Self {
+        let operation = Operations::from_text(step.op.as_str());
+        let params = match &operation {
+            // Opcodes the parser does not know about are labeled from the operand table
+            Operations::OTHER(op) => operand_params(op, step.stack.as_deref()),
+            _ => operation.parse_args(step.stack.clone()),
+        };
+        Self {
+            operation,
+            params,
+            remaining_gas: step.gas,
+            gas_cost: step.gas_cost,
+            expansion_cost: gas::expansion_cost(step),
+            pc: step.pc,
+            stack: step.stack.clone(),
+        }
+    }
+}
+
+/// What an operation did to the memory of its frame
+struct StepCount {
+    /// Status of the slots the previous operation of the frame touched
+    status: SlotStatus,
+    /// Number of slots touched
+    touched: usize,
+    /// Number of slots memory expanded by
+    new_slots: usize,
+}
+
+impl StepCount {
+    /// Appends the chart points of the operation, both datasets are cumulative
+    fn push_points(
+        &self,
+        operation_number: f64,
+        read_dataset: &mut Vec<(f64, f64)>,
+        write_dataset: &mut Vec<(f64, f64)>,
+    ) {
+        let last_read = read_dataset.last().map_or(0.0, |point| point.1);
+        let last_write = write_dataset.last().map_or(0.0, |point| point.1);
+        let (read, write) = match self.status {
+            SlotStatus::Reading => {
+                let read = last_read + self.touched as f64;
+                match self.new_slots {
+                    0 => (read, last_write),
+                    new_slots => (read, read + new_slots as f64),
+                }
+            }
+            SlotStatus::Writing => (last_read, last_write + self.touched as f64),
+            _ => (last_read, last_write),
+        };
+        read_dataset.push((operation_number, read));
+        write_dataset.push((operation_number, write));
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SlotStatus {
     Init,
@@ -173,366 +462,283 @@ impl SlotStatus {
     }
 
     pub fn from_opcode(op: &Operations) -> SlotStatus {
-        match op {
-            Operations::CALLDATACOPY => SlotStatus::Writing,
-            Operations::MSTORE => SlotStatus::Writing,
-            Operations::MSTORE8 => SlotStatus::Writing,
-            Operations::MLOAD => SlotStatus::Reading,
-            Operations::MSIZE => SlotStatus::Reading,
-            Operations::EXTCODECOPY => SlotStatus::Writing,
-            Operations::CODECOPY => SlotStatus::Writing,
-            Operations::RETURNDATACOPY => SlotStatus::Writing,
-            Operations::MCOPY => SlotStatus::Writing,
-            Operations::OTHER(_) => SlotStatus::Empty,
+        match &*op.text() {
+            // Storage is coloured like memory
+            "SLOAD" | "TLOAD" => SlotStatus::Reading,
+            "SSTORE" | "TSTORE" => SlotStatus::Writing,
+            op => access::status(op),
         }
     }
-}
 
-impl TransactionState {
-    pub async fn initialize(&mut self, transaction: TxHash, rpc: &str) -> Result<(), eyre::Error> {
-        let provider = provider::HTTPProvider::init(rpc).await?;
-        let transaction_result = provider.get_transaction_by_hash(transaction).await?;
-        self.transaction = transaction_result;
-        let opts = GethDebugTracingOptions {
-            config: GethDefaultTracingOptions {
-                enable_memory: Some(true),
-                disable_memory: None,
-                disable_stack: Some(false),
-                disable_storage: Some(true),
-                enable_return_data: Some(true),
-                disable_return_data: Some(false),
-                debug: None,
-                limit: None,
-            },
-            tracer: None,
-            tracer_config: trace::geth::GethDebugTracerConfig(serde_json::Value::Null),
-            timeout: None,
-        };
-
-        let result = provider.debug_trace_transaction(transaction, opts).await?;
-
-        if let GethTrace::JS(context) = result {
-            self.transaction_success = !serde_json::from_value(context["failed"].clone())?;
-            self.raw_data = serde_json::from_value(context["structLogs"].clone())?;
-            let max_memory_length = self
-                .raw_data
-                .iter()
-                .filter(|operation| operation.memory.is_some())
-                .map(|operation| operation.memory.as_ref().unwrap().len())
-                .max()
-                .unwrap_or(0);
-            self.slots = vec![SlotStatus::Empty; max_memory_length];
+    /// Status of a slot one operation after it was last touched
+    pub fn settle(self) -> SlotStatus {
+        match self {
+            SlotStatus::Init | SlotStatus::Reading => SlotStatus::Active,
+            SlotStatus::Writing => SlotStatus::Unread,
+            status => status,
         }
+    }
+}
 
-        Ok(())
+impl FrameState {
+    /// Grows the frame to the length of its memory, returns the number of new slots
+    fn grow(&mut self, memory_length: usize) -> usize {
+        let new_slots = memory_length.saturating_sub(self.slots.len());
+        // Memory expands in whole words, so a new slot is only marked as read or written if the
+        // access actually covers it
+        self.slots.resize(self.slots.len() + new_slots, SlotStatus::Active);
+        if let Some(bytes) = &mut self.byte_statuses {
+            bytes.resize(self.slots.len() * 32, SlotStatus::Active);
+        }
+        new_slots
     }
 
-    fn go_back(&mut self, iteration: u64) -> Result<&mut Self, eyre::Error> {
-        // go back one iteration
-        // determin the operation to index
-        // determin the slot status by checking the previous operation that interacted with the
-        // memory
-        let to_index = self.next_operation - iteration;
-        let last_memory_affecting_op_index = self.raw_data[..to_index as usize]
-            .iter()
-            .rev()
-            .enumerate()
-            .find_map(|(index, operation)| match Operations::from_text(operation.op.as_str()) {
-                Operations::OTHER(_) => None,
-                _ => Some(index),
-            })
-            .map(|index| to_index as usize - index - 1);
-
-        if last_memory_affecting_op_index.is_none() {
-            Ok(self)
-        } else {
-            let operation = &self.raw_data[last_memory_affecting_op_index.unwrap()];
-            self.next_slot_status =
-                SlotStatus::from_opcode(&Operations::from_text(operation.op.as_str()));
-            self.next_operation = last_memory_affecting_op_index.unwrap() as u64 + 2;
-            for (index, slot) in self.slots.iter_mut().enumerate() {
-                if index
-                    >= self.raw_data[last_memory_affecting_op_index.unwrap() + 1]
-                        .memory
-                        .as_deref()
-                        .unwrap()
-                        .len()
-                {
-                    *slot = SlotStatus::Empty;
-                } else if index
-                    < self.raw_data[last_memory_affecting_op_index.unwrap() + 1]
-                        .memory
-                        .as_deref()
-                        .unwrap()
-                        .len()
-                    && index >= operation.memory.as_deref().unwrap().len()
-                {
-                    *slot = self.next_slot_status;
+    /// Marks the slots touched by the last operation of the frame
+    fn apply_pending_access(&mut self) {
+        // Writes are applied last so that they win over reads of the same slot
+        let access = std::mem::take(&mut self.pending_access);
+        for (ranges, status) in
+            [(&access.reads, SlotStatus::Reading), (&access.writes, SlotStatus::Writing)]
+        {
+            for slot in ranges.iter().flat_map(access::slots) {
+                if let Some(slot) = self.slots.get_mut(slot) {
+                    *slot = status;
+                }
+            }
+            if let Some(bytes) = &mut self.byte_statuses {
+                for range in ranges {
+                    let end = range.end.min(bytes.len());
+                    bytes[range.start.min(end)..end].fill(status);
                 }
             }
-            self.operation_codes.pop();
-            Ok(self)
         }
     }
+}
 
-    fn go_forward(&mut self, iteration: u64) -> Result<&mut Self, eyre::Error> {
-        let range_ending = self.raw_data.len() as u64;
+impl TransactionState {
+    pub async fn initialize(&mut self, source: &TraceSource) -> Result<(), eyre::Error> {
+        self.load(source.fetch().await?);
+        Ok(())
+    }
 
-        for slot in &mut self.slots {
-            if *slot == SlotStatus::Init || *slot == SlotStatus::Reading {
-                *slot = SlotStatus::Active;
-            }
-            if *slot == SlotStatus::Writing {
-                *slot = SlotStatus::Unread;
+    fn load(&mut self, data: TraceData) {
+        self.transaction = data.transaction;
+        self.transaction_success = data.success;
+        self.raw_data = data.struct_logs;
+        self.call_tree =
+            CallTree::build(&self.transaction, self.transaction_success, &self.raw_data);
+        self.storage = StorageLog::build(&self.raw_data, &self.call_tree, StorageKind::Persistent);
+        self.transient_storage =
+            StorageLog::build(&self.raw_data, &self.call_tree, StorageKind::Transient);
+        self.return_data = ReturnData::build(&self.raw_data, &self.call_tree);
+        self.record_timeline();
+    }
+
+    /// Clears everything processed so far, as if playback had not started
+    fn reset(&mut self) {
+        self.next_operation = 0;
+        self.operation_codes.clear();
+        self.read_dataset.clear();
+        self.write_dataset.clear();
+        self.expansion_dataset.clear();
+        self.operation_to_render = OperationData::default();
+        self.frames.clear();
+        self.enter_frame(1);
+    }
+
+    /// Moves playback so that the given operation is the last one processed
+    pub fn seek(&mut self, operation: usize) -> Result<&mut Self, eyre::Error> {
+        self.materialize(operation.saturating_add(1));
+        Ok(self)
+    }
+
+    /// Index in `frames` of the frame the view is scoped to, while playback is inside it
+    fn scoped_frame(&self) -> Option<usize> {
+        let node = self.call_tree.nodes.get(self.scope?)?;
+        let operation = self.next_operation.checked_sub(1)? as usize;
+        let depth = node.depth as usize;
+        (node.contains(operation) && self.frames.len() >= depth).then_some(depth - 1)
+    }
+
+    /// Frames shown for the given view, outermost first. When the view is scoped to a call,
+    /// its frame takes the place of the innermost one
+    pub fn viewed_frames(&self, view: FrameView) -> Vec<&FrameState> {
+        let innermost = self.scoped_frame().unwrap_or(self.frames.len().saturating_sub(1));
+        match view {
+            FrameView::Current => self.frames.get(innermost).into_iter().collect(),
+            FrameView::Caller(offset) => {
+                self.frames.get(innermost.saturating_sub(offset)).into_iter().collect()
             }
+            FrameView::All => self.frames.iter().collect(),
         }
+    }
 
-        for operation_number in self.next_operation..range_ending {
-            // going through all opcodes
-            let operation = self.raw_data[operation_number as usize].clone();
-            if self.next_slot_status != SlotStatus::Empty
-                && self.next_slot_status != SlotStatus::Init
-            {
-                // Condition to check if the memory is affected in this operation as a result of the
-                // previous operation Memory is affected
-                let memory = operation.memory.as_ref().unwrap();
-                let mut new_slots = 0;
-
-                // handle the new slots
-                if memory.len() as u64 > self.indexed_slots_count {
-                    new_slots = memory.len() as u64 - self.indexed_slots_count
-                }
+    /// History of opcodes, limited to the operations of the scoped call if there is one
+    pub fn history(&self) -> Vec<&Operations> {
+        let node = self.scope.and_then(|scope| self.call_tree.nodes.get(scope));
+        self.operation_codes
+            .iter()
+            .filter(|(operation, _)| {
+                node.map_or(true, |node| {
+                    node.contains(*operation) && self.raw_data[*operation].depth == node.depth
+                })
+            })
+            .map(|(_, op)| op)
+            .collect()
+    }
 
-                for _ in 0..new_slots {
-                    // New slots can only be write operations
-                    self.slots[self.indexed_slots_count as usize] = self.next_slot_status;
-                    self.indexed_slots_count += 1;
-                }
+    /// Moves the cursor of the call tree panel by the given number of visible rows
+    pub fn move_call_cursor(&mut self, rows: isize) {
+        let visible = self.call_tree.visible(&self.collapsed_calls);
+        let position = visible.iter().position(|node| *node == self.selected_call).unwrap_or(0);
+        let position = position.saturating_add_signed(rows).min(visible.len().saturating_sub(1));
+        self.selected_call = visible.get(position).copied().unwrap_or_default();
+    }
 
-                match self.next_slot_status {
-                    SlotStatus::Reading => {
-                        let new_number = self.read_dataset.last().unwrap_or(&(0.0, 0.0)).1
-                            + new_slots as f64
-                            + self.slot_indexes_to_change_status.len() as f64;
-                        self.read_dataset.push((operation_number as f64, new_number));
-
-                        if new_slots > 0 {
-                            self.write_dataset.push((
-                                operation_number as f64,
-                                self.read_dataset.last().unwrap_or(&(0.0, 0.0)).1
-                                    + new_slots as f64,
-                            ));
-                        } else {
-                            let last_write = match self.write_dataset.last() {
-                                Some(&value) => (operation_number as f64, value.1),
-                                None => {
-                                    // Handle the case when the vector is empty
-                                    // For example, you might want to return an error or use a
-                                    // default value.
-                                    // Here, we'll just use a default value of 0.
-                                    (operation_number as f64, 0.0)
-                                }
-                            };
-                            self.write_dataset.push(last_write);
-                        }
-                    }
-                    SlotStatus::Writing => {
-                        let new_number = self.write_dataset.last().unwrap_or(&(0.0, 0.0)).1
-                            + new_slots as f64
-                            + self.slot_indexes_to_change_status.len() as f64;
-                        self.write_dataset.push((operation_number as f64, new_number));
-                        let last_read = match self.read_dataset.last() {
-                            Some(&value) => (operation_number as f64, value.1),
-                            None => {
-                                // Handle the case when the vector is empty
-                                // For example, you might want to return an error or use a default
-                                // value. Here, we'll just use a
-                                // default value of 0.
-                                (operation_number as f64, 0.0)
-                            }
-                        };
-                        self.read_dataset.push(last_read);
-                    }
-                    _ => {}
-                }
+    /// Hides the sub calls of the selected call, or moves the cursor to its caller if there is
+    /// nothing left to hide
+    pub fn collapse_call(&mut self) {
+        let Some(node) = self.call_tree.nodes.get(self.selected_call) else {
+            return;
+        };
+        if !node.children.is_empty() && !self.collapsed_calls.contains(&self.selected_call) {
+            self.collapsed_calls.push(self.selected_call);
+        } else if let Some(parent) = node.parent {
+            self.selected_call = parent;
+        }
+    }
 
-                // handle slots that need to have their status changed but aren't new
-                for index in self.slot_indexes_to_change_status.clone() {
-                    if index < 0 {
-                        // It's a read from MCOPY
-                        self.slots[-index as usize] = SlotStatus::Reading;
-                        continue;
-                    }
-                    self.slots[index as usize] = self.next_slot_status;
-                }
+    pub fn expand_call(&mut self) {
+        self.collapsed_calls.retain(|node| *node != self.selected_call);
+    }
 
-                self.slot_indexes_to_change_status = vec![];
-            } else {
-                let last_write = match self.write_dataset.last() {
-                    Some(&value) => (operation_number as f64, value.1),
-                    None => {
-                        // Handle the case when the vector is empty
-                        // For example, you might want to return an error or use a default value.
-                        // Here, we'll just use a default value of 0.
-                        (operation_number as f64, 0.0)
-                    }
-                };
-                let last_read = match self.read_dataset.last() {
-                    Some(&value) => (operation_number as f64, value.1),
-                    None => {
-                        // Handle the case when the vector is empty
-                        // For example, you might want to return an error or use a default value.
-                        // Here, we'll just use a default value of 0.
-                        (operation_number as f64, 0.0)
-                    }
-                };
+    /// Jumps to the first operation of the selected call and scopes the view to it, selecting
+    /// the transaction itself removes the scope
+    pub fn enter_call(&mut self) -> Result<&mut Self, eyre::Error> {
+        let Some(node) = self.call_tree.nodes.get(self.selected_call) else {
+            return Ok(self);
+        };
+        let first_step = node.first_step;
+        self.scope = node.parent.map(|_| self.selected_call);
+        self.seek(first_step)
+    }
 
-                self.write_dataset.push(last_write);
-                self.read_dataset.push(last_read);
-            }
+    /// Pops the frames that returned and pushes the ones that were entered, so that the
+    /// innermost frame has the given depth
+    fn enter_frame(&mut self, depth: u64) {
+        let depth = depth.max(1) as usize;
+        self.frames.truncate(depth);
+        while self.frames.len() < depth {
+            self.frames.push(FrameState {
+                depth: self.frames.len() as u64 + 1,
+                last_operation: self.next_operation as usize,
+                byte_statuses: self.track_bytes.then(Vec::new),
+                ..Default::default()
+            });
+        }
+    }
 
-            // push opcode to history
-            if operation_number != 0 {
-                self.operation_codes.push(Operations::from_text(
-                    self.raw_data[(operation_number - 1) as usize].op.as_str(),
-                ));
+    /// Processes the next operation on the call frames and returns what it did to memory
+    fn step(&mut self) -> StepCount {
+        for frame in &mut self.frames {
+            for slot in frame.slots.iter_mut().chain(frame.byte_statuses.iter_mut().flatten()) {
+                *slot = slot.settle();
             }
+        }
 
-            match Operations::from_text(operation.op.as_str()) {
-                Operations::OTHER(op) => {
-                    let operation_text = Operations::from_text(&op); // Reuse the result
-                    self.operation_to_render = OperationData {
-                        operation: operation_text.clone(), // Clone to avoid moving
-                        remaining_gas: operation.gas,
-                        gas_cost: operation.gas_cost,
-                        pc: operation.pc,
-                        params: HashMap::new(),
-                        stack: operation.stack,
-                    };
-                    self.next_slot_status = SlotStatus::Empty;
-                }
-                _ => {
-                    let operation_text = Operations::from_text(operation.op.as_str()); // Only one call
-                    self.handle_opcode(operation_text.clone(), operation.stack.clone());
-                    self.operation_to_render = OperationData {
-                        operation: operation_text.clone(),
-                        remaining_gas: operation.gas,
-                        gas_cost: operation.gas_cost,
-                        pc: operation.pc,
-                        params: operation_text.parse_args(operation.stack.clone()),
-                        stack: operation.stack,
-                    };
-                }
-            }
+        let operation_number = self.next_operation as usize;
+        self.enter_frame(self.raw_data[operation_number].depth);
+        let operation = &self.raw_data[operation_number];
+        let frame = self.frames.last_mut().unwrap();
+        frame.last_operation = operation_number;
+        let new_slots = frame.grow(operation.memory.as_ref().map_or(0, Vec::len));
+        let mut count = StepCount { status: frame.next_slot_status, touched: 0, new_slots };
 
-            // exit if it's the last iter
-            if operation_number - self.next_operation + 1 >= iteration {
-                self.next_operation = operation_number + 1;
-                break;
-            }
+        if count.status != SlotStatus::Empty && count.status != SlotStatus::Init {
+            // Memory is affected in this operation as a result of the previous operation of
+            // the frame. Memory only ever expands to cover an access, so the new slots are
+            // among the touched ones
+            count.touched = frame.pending_access.slot_count().max(new_slots);
+            frame.apply_pending_access();
         }
 
-        Ok(self)
+        let stack = operation.stack.as_deref().unwrap_or_default();
+        frame.pending_access =
+            MemoryAccess::from_operation(&operation.op, stack, frame.slots.len() * 32);
+        frame.next_slot_status = frame.pending_access.status();
+        self.next_operation += 1;
+        count
     }
 
-    fn handle_opcode(&mut self, opcode: Operations, stack: Option<Vec<U256>>) {
-        self.next_slot_status = SlotStatus::from_opcode(&opcode);
-        // handle other changes that are applied to the already existing slots
-        match opcode {
-            Operations::MCOPY => {
-                let unwrapped_stack = stack.as_ref().unwrap();
-                let memory_offset = unwrapped_stack.last().unwrap() / Uint::from(32);
-                let copy_offset =
-                    unwrapped_stack.get(unwrapped_stack.len() - 2).unwrap() / Uint::from(32);
-                let memory_end = ((unwrapped_stack.get(unwrapped_stack.len() - 3).unwrap())
-                    + Uint::from(31))
-                    / Uint::from(32);
-                for i in memory_offset.to::<i64>()
-                    ..=(memory_offset + memory_end - Uint::from(1)).to::<i64>()
-                {
-                    self.slot_indexes_to_change_status.push(i);
-                }
-                for i in
-                    copy_offset.to::<i64>()..=(copy_offset + memory_end - Uint::from(1)).to::<i64>()
-                {
-                    self.slot_indexes_to_change_status.push(-i);
-                }
-            }
-            Operations::MSTORE => {
-                let unwrapped_stack = stack.as_ref().unwrap();
-                let memory_offset = unwrapped_stack.last().unwrap() / Uint::from(32);
-                self.slot_indexes_to_change_status = vec![memory_offset.to::<i64>()];
-            }
-            Operations::EXTCODECOPY => {
-                let unwrapped_stack = stack.as_ref().unwrap();
-                let memory_offset =
-                    unwrapped_stack.get(unwrapped_stack.len() - 2).unwrap() / Uint::from(32);
-                let memory_end = (unwrapped_stack.get(unwrapped_stack.len() - 4).unwrap()
-                    + Uint::from(31))
-                    / Uint::from(32);
-                for i in memory_offset.to::<i64>()
-                    ..=(memory_offset + memory_end - Uint::from(1)).to::<i64>()
-                {
-                    self.slot_indexes_to_change_status.push(i);
-                }
-            }
-            Operations::CODECOPY => {
-                let unwrapped_stack = stack.as_ref().unwrap();
-                let memory_offset = unwrapped_stack.last().unwrap() / Uint::from(32);
-                let memory_end = ((unwrapped_stack.get(unwrapped_stack.len() - 3).unwrap())
-                    + Uint::from(31))
-                    / Uint::from(32);
-                for i in memory_offset.to::<i64>()
-                    ..=(memory_offset + memory_end - Uint::from(1)).to::<i64>()
-                {
-                    self.slot_indexes_to_change_status.push(i);
-                }
-            }
-            Operations::RETURNDATACOPY => {
-                let unwrapped_stack = stack.as_ref().unwrap();
-                let memory_offset = unwrapped_stack.last().unwrap() / Uint::from(32);
-                let memory_end = ((unwrapped_stack.get(unwrapped_stack.len() - 3).unwrap())
-                    + Uint::from(31))
-                    / Uint::from(32);
-                for i in memory_offset.to::<i64>()
-                    ..=(memory_offset + memory_end - Uint::from(1)).to::<i64>()
-                {
-                    self.slot_indexes_to_change_status.push(i);
-                }
-            }
-            Operations::MSTORE8 => {
-                // Writes one byte value
-                // TODO: Check what happens if the offset is at the end of a slot and the value
-                // needs more space, does it overflow to the next slot?
-                let unwrapped_stack: &Vec<Uint<256, 4>> = stack.as_ref().unwrap();
-                let memory_offset = unwrapped_stack.last().unwrap() / Uint::from(32);
-                self.slot_indexes_to_change_status = vec![memory_offset.saturating_to::<i64>()];
-            }
-            Operations::MLOAD => {
-                let unwrapped_stack: &Vec<Uint<256, 4>> = stack.as_ref().unwrap();
-                let memory_offset = unwrapped_stack.last().unwrap() / Uint::from(32);
-                self.slot_indexes_to_change_status = vec![memory_offset.to::<i64>()];
-            }
-            Operations::CALLDATACOPY => {
-                let unwrapped_stack = stack.as_ref().unwrap();
-                let memory_offset = unwrapped_stack.last().unwrap() / Uint::from(32);
-                let memory_end = ((unwrapped_stack.get(unwrapped_stack.len() - 3).unwrap())
-                    + Uint::from(31))
-                    / Uint::from(32);
-                for i in memory_offset.to::<i64>()
-                    ..=(memory_offset + memory_end - Uint::from(1)).to::<i64>()
-                {
-                    self.slot_indexes_to_change_status.push(i);
-                }
-            }
-            Operations::MSIZE => {
-                self.slot_indexes_to_change_status =
-                    (0..self.indexed_slots_count).map(|x| x as i64).collect();
+    /// Plays the whole transaction once to record the checkpoints and chart points of the
+    /// timeline, then returns to the start
+    fn record_timeline(&mut self) {
+        let mut timeline = Timeline::default();
+        self.reset();
+        while (self.next_operation as usize) < self.raw_data.len() {
+            timeline.record(self.next_operation as usize, &self.frames);
+            let operation_number = self.next_operation as f64;
+            let expansion = gas::expansion_cost(&self.raw_data[self.next_operation as usize]);
+            let count = self.step();
+            count.push_points(
+                operation_number,
+                &mut timeline.read_dataset,
+                &mut timeline.write_dataset,
+            );
+            let total = timeline.expansion_dataset.last().map_or(0.0, |point| point.1);
+            timeline.expansion_dataset.push((operation_number, total + expansion as f64));
+        }
+        self.timeline = Arc::new(timeline);
+        self.reset();
+    }
+
+    /// Rebuilds the state after the given number of operations from the closest checkpoint,
+    /// unless it is only a few operations ahead
+    fn materialize(&mut self, next_operation: usize) {
+        let target = next_operation.min(self.raw_data.len());
+        let current = self.next_operation as usize;
+        if target < current || target - current > CHECKPOINT_INTERVAL {
+            if let Some((operation, frames)) = self.timeline.checkpoint(target) {
+                self.frames = frames;
+                self.next_operation = operation as u64;
             }
-            Operations::OTHER(_) => {}
+        }
+        while (self.next_operation as usize) < target {
+            self.step();
+        }
+
+        // The history holds every operation before the current one
+        let history_length = target.saturating_sub(1);
+        self.operation_codes.truncate(history_length);
+        for index in self.operation_codes.len()..history_length {
+            self.operation_codes.push((index, Operations::from_text(&self.raw_data[index].op)));
+        }
+        for (dataset, points) in [
+            (&mut self.read_dataset, &self.timeline.read_dataset),
+            (&mut self.write_dataset, &self.timeline.write_dataset),
+            (&mut self.expansion_dataset, &self.timeline.expansion_dataset),
+        ] {
+            dataset.truncate(target);
+            dataset.extend_from_slice(&points[dataset.len()..target.min(points.len())]);
+        }
+        self.operation_to_render = match target.checked_sub(1) {
+            Some(index) => OperationData::from_step(&self.raw_data[index]),
+            None => OperationData::default(),
         };
     }
 
+    fn go_back(&mut self, iteration: u64) -> Result<&mut Self, eyre::Error> {
+        // The first operation stays on screen
+        let target = self.next_operation.saturating_sub(iteration).max(1);
+        self.materialize(target as usize);
+        Ok(self)
+    }
+
+    fn go_forward(&mut self, iteration: u64) -> Result<&mut Self, eyre::Error> {
+        self.materialize(self.next_operation.saturating_add(iteration) as usize);
+        Ok(self)
+    }
+
     pub async fn run(&mut self, iteration: u64, forward: bool) -> Result<&mut Self, eyre::Error> {
         if !forward {
             return self.go_back(iteration);
@@ -541,3 +747,292 @@ impl TransactionState {
         self.go_forward(iteration)
     }
 }
+
+/// Numbered list of breakpoints or watchpoints for the prompt line
+fn list(name: &str, items: &[impl std::fmt::Display]) -> String {
+    if items.is_empty() {
+        return format!("No {}", name);
+    }
+    items.iter().enumerate().map(|(index, item)| format!("{}: {}", index, item)).join(", ")
+}
+
+/// Names the stack operands of an opcode, top of the stack first
+fn operand_params(op: &str, stack: Option<&[U256]>) -> HashMap<String, String> {
+    let stack = stack.unwrap_or_default();
+    opcodes::operands(op)
+        .into_iter()
+        .zip(stack.iter().rev())
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{self, with_memory};
+
+    /// A struct log step with the given stack, top of the stack last, and memory size in words
+    fn step(op: &str, stack: &[u64], memory_words: usize) -> serde_json::Value {
+        step_at(1, op, stack, memory_words)
+    }
+
+    fn step_at(depth: u64, op: &str, stack: &[u64], memory_words: usize) -> serde_json::Value {
+        with_memory(test_utils::step(depth, op, stack), memory_words)
+    }
+
+    /// Loads a hand-written trace with byte statuses enabled, without processing any step
+    fn load(steps: Vec<serde_json::Value>) -> TransactionState {
+        let mut state = TransactionState { track_bytes: true, ..Default::default() };
+        state.load(TraceData { struct_logs: test_utils::struct_logs(steps), ..Default::default() });
+        state
+    }
+
+    /// Processes every step of a hand-written trace with byte statuses enabled
+    fn process(steps: Vec<serde_json::Value>) -> TransactionState {
+        let mut state = load(steps);
+        for _ in 0..state.raw_data.len() {
+            state.go_forward(1).unwrap();
+        }
+        state
+    }
+
+    /// Processes every step of a hand-written trace and returns the slot statuses of the
+    /// innermost frame
+    fn run_trace(steps: Vec<serde_json::Value>) -> Vec<SlotStatus> {
+        process(steps).frames.pop().unwrap().slots
+    }
+
+    #[test]
+    fn unaligned_mstore_writes_both_words() {
+        let slots = run_trace(vec![step("MSTORE", &[0x2a, 0x10], 0), step("STOP", &[], 2)]);
+        assert_eq!(slots, vec![SlotStatus::Writing, SlotStatus::Writing]);
+    }
+
+    #[test]
+    fn aligned_mstore_writes_one_word() {
+        let slots = run_trace(vec![
+            step("MSTORE", &[1, 0x00], 0),
+            step("MSTORE", &[2, 0x20], 1),
+            step("STOP", &[], 2),
+        ]);
+        assert_eq!(slots, vec![SlotStatus::Unread, SlotStatus::Writing]);
+    }
+
+    #[test]
+    fn expansion_past_an_untouched_word_leaves_it_active() {
+        let slots = run_trace(vec![step("MSTORE", &[1, 0x20], 0), step("STOP", &[], 2)]);
+        assert_eq!(slots, vec![SlotStatus::Active, SlotStatus::Writing]);
+    }
+
+    #[test]
+    fn mstore8_at_the_end_of_a_word_writes_only_that_word() {
+        let slots = run_trace(vec![
+            step("MSTORE", &[1, 0x20], 0),
+            step("MSTORE8", &[0xff, 0x1f], 2),
+            step("STOP", &[], 2),
+        ]);
+        assert_eq!(slots, vec![SlotStatus::Writing, SlotStatus::Unread]);
+    }
+
+    #[test]
+    fn unaligned_mload_reads_both_words() {
+        let slots = run_trace(vec![
+            step("MSTORE", &[1, 0x20], 0),
+            step("MLOAD", &[0x10], 2),
+            step("STOP", &[], 2),
+        ]);
+        assert_eq!(slots, vec![SlotStatus::Reading, SlotStatus::Reading]);
+    }
+
+    #[test]
+    fn zero_length_copy_touches_nothing() {
+        let slots = run_trace(vec![
+            step("MSTORE", &[1, 0x00], 0),
+            step("CALLDATACOPY", &[0, 0, 0x40], 1),
+            step("STOP", &[], 1),
+        ]);
+        assert_eq!(slots, vec![SlotStatus::Unread]);
+    }
+
+    #[test]
+    fn partial_copy_writes_only_the_words_it_overlaps() {
+        let slots = run_trace(vec![
+            step("MSTORE", &[1, 0x40], 0),
+            step("CODECOPY", &[0x20, 0, 0x08], 3),
+            step("STOP", &[], 3),
+        ]);
+        assert_eq!(slots, vec![SlotStatus::Writing, SlotStatus::Writing, SlotStatus::Unread]);
+    }
+
+    #[test]
+    fn mcopy_from_the_first_word_reads_it() {
+        let slots = run_trace(vec![
+            step("MSTORE", &[1, 0x20], 0),
+            step("MCOPY", &[0x20, 0x00, 0x20], 2),
+            step("STOP", &[], 2),
+        ]);
+        assert_eq!(slots, vec![SlotStatus::Reading, SlotStatus::Writing]);
+    }
+
+    #[test]
+    fn mstore8_writes_a_single_byte() {
+        let state = process(vec![
+            step("MSTORE", &[1, 0x00], 0),
+            step("MSTORE8", &[0xff, 0x05], 1),
+            step("STOP", &[], 1),
+        ]);
+        let bytes = state.frames[0].byte_statuses.clone().unwrap();
+        assert_eq!(bytes[5], SlotStatus::Writing);
+        assert!(bytes
+            .iter()
+            .enumerate()
+            .all(|(index, byte)| index == 5 || *byte == SlotStatus::Unread));
+    }
+
+    #[test]
+    fn callee_memory_does_not_bleed_into_the_caller() {
+        let state = process(vec![
+            step("MSTORE", &[1, 0x00], 0),
+            // Calls 0xaa without arguments and a 32 byte return buffer at 0x20
+            step("CALL", &[0x20, 0x20, 0, 0, 0, 0xaa, 0xffff], 1),
+            step_at(2, "MSTORE", &[2, 0x40], 0),
+            step_at(2, "RETURN", &[0x20, 0x40], 3),
+            step("STOP", &[], 2),
+        ]);
+        assert_eq!(state.frames.len(), 1);
+        assert_eq!(state.frames[0].slots, vec![SlotStatus::Unread, SlotStatus::Writing]);
+    }
+
+    #[test]
+    fn entering_a_call_scopes_the_view_to_its_frame() {
+        let mut state = process(vec![
+            step("MSTORE", &[1, 0x00], 0),
+            step("CALL", &[0x20, 0x20, 0, 0, 0, 0xaa, 0xffff], 1),
+            step_at(2, "MSTORE", &[2, 0x40], 0),
+            step_at(2, "RETURN", &[0x20, 0x40], 3),
+            step("STOP", &[1], 2),
+        ]);
+        let call = &state.call_tree.nodes[1];
+        assert_eq!((call.first_step, call.last_step, call.success), (2, 3, true));
+
+        state.selected_call = 1;
+        state.enter_call().unwrap();
+        assert_eq!(state.next_operation, 3);
+        assert_eq!(state.viewed_frames(FrameView::Current)[0].depth, 2);
+        state.seek(3).unwrap();
+        assert_eq!(state.history().len(), 1);
+    }
+
+    #[test]
+    fn stepping_back_matches_stepping_forward() {
+        // Long enough to cross a checkpoint, alternating reads and writes of the four words
+        let steps = std::iter::once(step("MSTORE", &[0, 0x60], 0))
+            .chain((1..CHECKPOINT_INTERVAL as u64 + 8).map(|index| match index % 2 {
+                0 => step("MSTORE", &[index, (index % 4) * 0x20], 4),
+                _ => step("MLOAD", &[(index % 4) * 0x20], 4),
+            }))
+            .collect();
+        let mut state = load(steps);
+
+        let mut forward = vec![];
+        for _ in 0..state.raw_data.len() {
+            state.go_forward(1).unwrap();
+            forward.push((
+                state.frames.clone(),
+                state.read_dataset.clone(),
+                state.write_dataset.clone(),
+                state.operation_codes.len(),
+            ));
+        }
+        for expected in forward.iter().rev().skip(1) {
+            state.go_back(1).unwrap();
+            assert_eq!(state.frames, expected.0);
+            assert_eq!(state.read_dataset, expected.1);
+            assert_eq!(state.write_dataset, expected.2);
+            assert_eq!(state.operation_codes.len(), expected.3);
+        }
+    }
+
+    #[tokio::test]
+    async fn breakpoints_stop_inside_an_iteration() {
+        let mut app_state = AppState {
+            transaction_states: vec![load(vec![
+                step("PUSH1", &[], 0),
+                step("MSTORE", &[1, 0x00], 0),
+                step("MSTORE", &[1, 0x20], 1),
+                step("STOP", &[], 2),
+            ])],
+            breakpoints: vec!["memory above 32".parse().unwrap()],
+            ..Default::default()
+        };
+        app_state.run(4, true, false).await.unwrap();
+        assert!(app_state.pause);
+        assert_eq!(app_state.transaction_states[0].next_operation, 4);
+        let message = app_state.message.unwrap();
+        assert_eq!(message, "Breakpoint 0 (memory above 32) hit at step 3");
+    }
+
+    #[tokio::test]
+    async fn lasting_conditions_only_fire_when_they_start_to_hold() {
+        let mut app_state = AppState {
+            transaction_states: vec![load(vec![
+                step("MSTORE", &[1, 0x20], 0),
+                step("POP", &[], 2),
+                step("POP", &[], 2),
+                step("STOP", &[], 2),
+            ])],
+            breakpoints: vec!["memory above 32".parse().unwrap()],
+            ..Default::default()
+        };
+        app_state.run(4, true, false).await.unwrap();
+        assert_eq!(app_state.transaction_states[0].next_operation, 2);
+        app_state.message = None;
+        app_state.run(4, true, false).await.unwrap();
+        assert!(!app_state.pause);
+        assert_eq!(app_state.message, None);
+        assert_eq!(app_state.transaction_states[0].next_operation, 4);
+    }
+
+    #[tokio::test]
+    async fn transactions_side_by_side_stop_at_the_same_operation() {
+        let mut app_state = AppState {
+            transaction_states: vec![
+                load(vec![
+                    step("PUSH1", &[], 0),
+                    step("PUSH1", &[], 0),
+                    step("MSTORE", &[1, 0], 0),
+                    step("STOP", &[], 1),
+                ]),
+                load(vec![step("PUSH1", &[], 0), step("MSTORE", &[1, 0], 0), step("STOP", &[], 1)]),
+            ],
+            breakpoints: vec!["op MSTORE".parse().unwrap()],
+            ..Default::default()
+        };
+        app_state.run(4, true, false).await.unwrap();
+        assert!(app_state.pause);
+        for state in &app_state.transaction_states {
+            assert_eq!(state.next_operation, 2);
+        }
+        let message = app_state.message.unwrap();
+        assert_eq!(message, "Transaction 1: Breakpoint 0 (op MSTORE) hit at step 1");
+    }
+
+    #[tokio::test]
+    async fn watchpoints_pause_on_the_operation_touching_the_words() {
+        let mut app_state = AppState {
+            transaction_states: vec![load(vec![
+                step("MSTORE", &[1, 0x00], 0),
+                step("MLOAD", &[0x00], 1),
+                step("MSTORE", &[0x80, 0x40], 1),
+                step("STOP", &[], 3),
+            ])],
+            watchpoints: vec!["fmp".parse().unwrap()],
+            ..Default::default()
+        };
+        app_state.run(4, true, false).await.unwrap();
+        assert_eq!(app_state.transaction_states[0].next_operation, 3);
+        let message = app_state.message.unwrap();
+        assert_eq!(message, "Watchpoint 0 (word 2) written by MSTORE at step 2");
+    }
+}
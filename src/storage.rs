@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use alloy::{
+    primitives::{Address, U256},
+    rpc::types::trace::geth::StructLog,
+};
+
+use crate::{access::arg, calltree::CallTree, state::SlotStatus};
+
+/// Extra gas SLOAD and SSTORE pay for a slot that was not accessed before (EIP-2929)
+static COLD_SLOAD_COST: u64 = 2100;
+
+/// Persistent storage or EIP-1153 transient storage
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StorageKind {
+    #[default]
+    Persistent,
+    /// Starts empty in every transaction and is cleared when it ends
+    Transient,
+}
+
+impl StorageKind {
+    /// Opcodes that load and store this kind of storage
+    pub fn opcodes(&self) -> (&'static str, &'static str) {
+        match self {
+            StorageKind::Persistent => ("SLOAD", "SSTORE"),
+            StorageKind::Transient => ("TLOAD", "TSTORE"),
+        }
+    }
+}
+
+/// A single load or store
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageAccess {
+    pub operation: usize,
+    /// Contract whose storage is accessed, the caller's for DELEGATECALL and CALLCODE frames
+    pub address: Option<Address>,
+    pub key: U256,
+    /// Reading for loads and Writing for stores
+    pub status: SlotStatus,
+    /// Value of the slot before the access, `None` if the trace does not tell
+    pub old: Option<U256>,
+    /// Value read or written, `None` if the trace does not tell
+    pub value: Option<U256>,
+    /// First access to the slot in the transaction, transient storage is never cold
+    pub cold: bool,
+    pub gas_cost: u64,
+    /// Operation from which the access is undone by a failed frame around it
+    pub reverted_at: Option<usize>,
+}
+
+/// A storage slot as it looks at some point of the transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageSlot {
+    pub address: Option<Address>,
+    pub key: U256,
+    pub value: Option<U256>,
+    pub reads: usize,
+    pub writes: usize,
+    /// Last access to the slot so far
+    pub last: StorageAccess,
+}
+
+/// Every storage access of a transaction with the cumulative counts charted next to memory
+#[derive(Debug, Clone, Default)]
+pub struct StorageLog {
+    pub kind: StorageKind,
+    /// Accesses in execution order
+    pub accesses: Vec<StorageAccess>,
+    /// Number of loads up to every operation
+    pub read_dataset: Vec<(f64, f64)>,
+    /// Number of stores up to every operation
+    pub write_dataset: Vec<(f64, f64)>,
+    /// Position of every slot in the order slots were first accessed
+    slot_indices: HashMap<(Option<Address>, U256), usize>,
+}
+
+impl StorageLog {
+    pub fn build(steps: &[StructLog], call_tree: &CallTree, kind: StorageKind) -> Self {
+        let mut log = Self { kind, ..Default::default() };
+        let (load, store) = kind.opcodes();
+        // Values every slot was seen with, each with the operation a failed frame undoes it from
+        let mut values: HashMap<(Option<Address>, U256), Vec<(U256, Option<usize>)>> =
+            HashMap::new();
+        let (mut reads, mut writes) = (0.0, 0.0);
+
+        for (index, step) in steps.iter().enumerate() {
+            let stack = step.stack.as_deref().unwrap_or_default();
+            let op = step.op.as_str();
+            if let (Some(key), true) = (stack.last().copied(), op == load || op == store) {
+                let node = call_tree.node_at(index, step.depth);
+                let address = call_tree.storage_address(node);
+                let reverted_at = call_tree.reverted_at(node);
+                let known = values.get(&(address, key)).and_then(|values| {
+                    values
+                        .iter()
+                        .rev()
+                        .find(|(_, reverted_at)| reverted_at.map_or(true, |at| at > index))
+                        .map(|(value, _)| *value)
+                });
+                let cold = match (kind, op == load) {
+                    (StorageKind::Transient, _) => false,
+                    (StorageKind::Persistent, true) => step.gas_cost >= COLD_SLOAD_COST,
+                    (StorageKind::Persistent, false) => matches!(
+                        step.gas_cost.checked_sub(COLD_SLOAD_COST),
+                        Some(100 | 2900 | 20000)
+                    ),
+                };
+                let access = match op == load {
+                    true => {
+                        reads += 1.0;
+                        // The loaded value is on top of the stack of the next step
+                        let value = steps
+                            .get(index + 1)
+                            .and_then(|next| next.stack.as_deref())
+                            .and_then(|stack| stack.last().copied());
+                        StorageAccess {
+                            operation: index,
+                            address,
+                            key,
+                            status: SlotStatus::Reading,
+                            old: value.or(known),
+                            value,
+                            cold,
+                            gas_cost: step.gas_cost,
+                            reverted_at,
+                        }
+                    }
+                    false => {
+                        writes += 1.0;
+                        let value = (stack.len() > 1).then(|| arg(stack, 1));
+                        let old = known.or(match kind {
+                            StorageKind::Transient => Some(U256::ZERO),
+                            // Without an earlier access the old value can only be told from the
+                            // EIP-2200 cost of the write
+                            StorageKind::Persistent => {
+                                match step.gas_cost - if cold { COLD_SLOAD_COST } else { 0 } {
+                                    20000 => Some(U256::ZERO),
+                                    100 => value,
+                                    _ => None,
+                                }
+                            }
+                        });
+                        StorageAccess {
+                            operation: index,
+                            address,
+                            key,
+                            status: SlotStatus::Writing,
+                            old,
+                            value,
+                            cold,
+                            gas_cost: step.gas_cost,
+                            reverted_at,
+                        }
+                    }
+                };
+                let slot_values = values.entry((address, key)).or_default();
+                // Without a value that still holds, the old value is the one from before the
+                // transaction, which no revert undoes
+                if let (None, Some(old)) = (known, access.old) {
+                    slot_values.push((old, None));
+                }
+                if let Some(value) = access.value {
+                    slot_values.push((value, reverted_at));
+                }
+                let next_index = log.slot_indices.len();
+                log.slot_indices.entry((address, key)).or_insert(next_index);
+                log.accesses.push(access);
+            }
+            log.read_dataset.push((index as f64, reads));
+            log.write_dataset.push((index as f64, writes));
+        }
+
+        log
+    }
+
+    /// Slots accessed up to the given operation, in the order they were first accessed
+    pub fn slots(&self, operation: usize) -> Vec<StorageSlot> {
+        let mut slots: Vec<StorageSlot> = vec![];
+        for access in self.accesses.iter().take_while(|access| access.operation <= operation) {
+            // Slots up to any operation are a prefix of the slots in first access order
+            let index = self.slot_indices[&(access.address, access.key)];
+            if index == slots.len() {
+                slots.push(StorageSlot {
+                    address: access.address,
+                    key: access.key,
+                    value: access.old,
+                    reads: 0,
+                    writes: 0,
+                    last: access.clone(),
+                });
+            }
+            let slot = &mut slots[index];
+            match access.status {
+                SlotStatus::Writing => slot.writes += 1,
+                _ => slot.reads += 1,
+            }
+            // Values from frames that failed by the operation are rolled back
+            if access.reverted_at.map_or(true, |at| at > operation) {
+                slot.value = access.value.or(slot.value);
+            }
+            slot.last = access.clone();
+        }
+        slots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::rpc::types::eth::Transaction;
+
+    use super::*;
+    use crate::test_utils::{step, struct_logs, with_gas_cost};
+
+    /// Struct logs of a single frame from the opcode, stack and gas cost of every step, top of
+    /// the stack last
+    fn steps(steps: &[(&str, &[u64], u64)]) -> Vec<StructLog> {
+        struct_logs(
+            steps
+                .iter()
+                .map(|(op, stack, gas_cost)| with_gas_cost(step(1, op, stack), *gas_cost))
+                .collect(),
+        )
+    }
+
+    fn build(steps: &[StructLog], kind: StorageKind) -> StorageLog {
+        let call_tree = CallTree::build(&Transaction::default(), true, steps);
+        StorageLog::build(steps, &call_tree, kind)
+    }
+
+    #[test]
+    fn old_values_come_from_earlier_accesses_or_gas() {
+        let storage = build(
+            &steps(&[
+                // A cold write to an empty slot
+                ("SSTORE", &[5, 1], 22100),
+                ("SLOAD", &[1], 100),
+                ("STOP", &[5], 0),
+            ]),
+            StorageKind::Persistent,
+        );
+        let slots = storage.slots(2);
+        assert_eq!(slots.len(), 1);
+        assert_eq!((slots[0].reads, slots[0].writes), (1, 1));
+        assert_eq!(slots[0].value, Some(U256::from(5)));
+        let write = &storage.accesses[0];
+        assert_eq!((write.old, write.cold), (Some(U256::ZERO), true));
+        assert!(!storage.accesses[1].cold);
+    }
+
+    #[test]
+    fn slots_keep_the_order_they_were_first_accessed_in() {
+        let storage = build(
+            &steps(&[
+                ("SSTORE", &[5, 2], 2900),
+                ("SSTORE", &[6, 1], 2900),
+                ("SLOAD", &[2], 100),
+                ("STOP", &[5], 0),
+            ]),
+            StorageKind::Persistent,
+        );
+        assert_eq!(storage.slots(0).len(), 1);
+        let slots = storage.slots(3);
+        let keys: Vec<U256> = slots.iter().map(|slot| slot.key).collect();
+        assert_eq!(keys, vec![U256::from(2), U256::from(1)]);
+        assert_eq!((slots[0].reads, slots[0].writes), (1, 1));
+        assert_eq!((slots[1].reads, slots[1].writes), (0, 1));
+    }
+
+    #[test]
+    fn transient_storage_starts_empty_and_is_never_cold() {
+        let steps = steps(&[("TSTORE", &[7, 1], 100), ("TLOAD", &[1], 100), ("STOP", &[7], 0)]);
+        let transient = build(&steps, StorageKind::Transient);
+        assert_eq!(transient.accesses.len(), 2);
+        let store = &transient.accesses[0];
+        assert_eq!((store.old, store.cold), (Some(U256::ZERO), false));
+        assert_eq!(transient.accesses[1].value, Some(U256::from(7)));
+        assert!(build(&steps, StorageKind::Persistent).accesses.is_empty());
+    }
+
+    #[test]
+    fn writes_of_reverted_frames_are_rolled_back() {
+        let mut steps = steps(&[
+            ("TSTORE", &[1, 7], 100),
+            ("CALL", &[0, 0, 0, 0, 0, 0xaa, 1000], 100),
+            ("TSTORE", &[2, 7], 100),
+            ("REVERT", &[0, 0], 0),
+            // The failed call leaves a zero on the stack of its caller
+            ("POP", &[0], 2),
+            ("TSTORE", &[3, 7], 100),
+            ("STOP", &[], 0),
+        ]);
+        steps[2].depth = 2;
+        steps[3].depth = 2;
+        let transient = build(&steps, StorageKind::Transient);
+        let reverted = &transient.accesses[1];
+        assert_eq!((reverted.old, reverted.reverted_at), (Some(U256::from(1)), Some(4)));
+        assert_eq!(transient.accesses[2].old, Some(U256::from(1)));
+        assert_eq!(transient.slots(3)[0].value, Some(U256::from(2)));
+        assert_eq!(transient.slots(4)[0].value, Some(U256::from(1)));
+        assert_eq!(transient.slots(5)[0].value, Some(U256::from(3)));
+    }
+}
@@ -0,0 +1,30 @@
+use alloy::rpc::types::trace::geth::StructLog;
+use serde_json::{json, Value};
+
+/// A hand-written struct log step at the given depth with the given stack, top of the stack
+/// last. It costs 3 gas and has no memory
+pub fn step(depth: u64, op: &str, stack: &[u64]) -> Value {
+    json!({
+        "pc": 0,
+        "op": op,
+        "gas": 100000,
+        "gasCost": 3,
+        "depth": depth,
+        "stack": stack.iter().map(|value| format!("{:#x}", value)).collect::<Vec<_>>(),
+    })
+}
+
+/// The step with zeroed memory of the given size in words
+pub fn with_memory(mut step: Value, words: usize) -> Value {
+    step["memory"] = json!(vec!["00".repeat(32); words]);
+    step
+}
+
+pub fn with_gas_cost(mut step: Value, gas_cost: u64) -> Value {
+    step["gasCost"] = json!(gas_cost);
+    step
+}
+
+pub fn struct_logs(steps: Vec<Value>) -> Vec<StructLog> {
+    serde_json::from_value(Value::Array(steps)).unwrap()
+}
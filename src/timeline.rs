@@ -0,0 +1,96 @@
+use std::ops::Range;
+
+use crate::state::{FrameState, SlotStatus};
+
+/// Number of operations between two checkpoints, rewinding replays at most this many
+pub static CHECKPOINT_INTERVAL: usize = 256;
+
+/// A call frame at a checkpoint. Byte statuses are not cloned: every status settles at the
+/// start of the next operation, after which a byte is either unread or active, so only the
+/// unread ranges are kept and the rest of the bytes are rebuilt as active
+#[derive(Debug, Clone)]
+struct FrameCheckpoint {
+    /// The frame without its byte statuses
+    frame: FrameState,
+    /// Number of tracked bytes and the ranges among them written but not read since
+    bytes: Option<(usize, Vec<Range<usize>>)>,
+}
+
+impl FrameCheckpoint {
+    fn new(frame: &FrameState) -> Self {
+        let bytes = frame.byte_statuses.as_ref().map(|bytes| {
+            let mut unread: Vec<Range<usize>> = vec![];
+            for (index, status) in bytes.iter().enumerate() {
+                if status.settle() != SlotStatus::Unread {
+                    continue;
+                }
+                match unread.last_mut() {
+                    Some(range) if range.end == index => range.end += 1,
+                    _ => unread.push(index..index + 1),
+                }
+            }
+            (bytes.len(), unread)
+        });
+        let frame = FrameState {
+            depth: frame.depth,
+            last_operation: frame.last_operation,
+            slots: frame.slots.clone(),
+            byte_statuses: None,
+            pending_access: frame.pending_access.clone(),
+            next_slot_status: frame.next_slot_status,
+        };
+        Self { frame, bytes }
+    }
+
+    /// The frame with its byte statuses as they are once the next operation settles them
+    fn restore(&self) -> FrameState {
+        let byte_statuses = self.bytes.as_ref().map(|(length, unread)| {
+            let mut bytes = vec![SlotStatus::Active; *length];
+            for range in unread {
+                bytes[range.clone()].fill(SlotStatus::Unread);
+            }
+            bytes
+        });
+        FrameState { byte_statuses, ..self.frame.clone() }
+    }
+}
+
+/// Everything needed to rebuild the state of a transaction at any operation, recorded by
+/// playing the transaction once when it is loaded.
+///
+/// A checkpoint costs the slots of the frames alive at it plus the unread byte ranges, so
+/// the timeline takes O(n / 256 · m) memory for n operations and m words of live memory.
+/// Rewinding restores a checkpoint and replays up to 256 operations, each of which settles
+/// every status of every frame, so a rewind is O(256 · m) work, or 32 times that with byte
+/// statuses
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    /// Call frames before every `CHECKPOINT_INTERVAL`-th operation, the first one is the state
+    /// before any operation ran
+    checkpoints: Vec<Vec<FrameCheckpoint>>,
+    /// Read chart point of every operation
+    pub read_dataset: Vec<(f64, f64)>,
+    /// Write chart point of every operation
+    pub write_dataset: Vec<(f64, f64)>,
+    /// Memory expansion gas paid up to every operation
+    pub expansion_dataset: Vec<(f64, f64)>,
+}
+
+impl Timeline {
+    /// Records the frames if a checkpoint is due before the given operation
+    pub fn record(&mut self, next_operation: usize, frames: &[FrameState]) {
+        if next_operation % CHECKPOINT_INTERVAL == 0 {
+            self.checkpoints.push(frames.iter().map(FrameCheckpoint::new).collect());
+        }
+    }
+
+    /// Latest checkpoint before the last of the given number of operations ran, with the
+    /// operation it was taken at. Byte statuses are only exact once an operation has been
+    /// replayed on top of a checkpoint, so a checkpoint is never the state itself
+    pub fn checkpoint(&self, next_operation: usize) -> Option<(usize, Vec<FrameState>)> {
+        let last = self.checkpoints.len().checked_sub(1)?;
+        let index = (next_operation.saturating_sub(1) / CHECKPOINT_INTERVAL).min(last);
+        let frames = self.checkpoints[index].iter().map(FrameCheckpoint::restore).collect();
+        Some((index * CHECKPOINT_INTERVAL, frames))
+    }
+}
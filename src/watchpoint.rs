@@ -0,0 +1,148 @@
+use std::{fmt, ops::Range, str::FromStr};
+
+use alloy::primitives::U256;
+use color_eyre::eyre::{self, eyre};
+
+use crate::{
+    access::{self, MemoryAccess},
+    number::parse_number,
+    state::{SlotStatus, TransactionState},
+};
+
+/// Word of memory holding the free memory pointer in Solidity and Vyper code
+pub static FREE_MEMORY_POINTER_WORD: usize = 2;
+
+/// Range of memory words that pauses playback whenever it is read or written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watchpoint {
+    pub words: Range<usize>,
+    /// Call tree node of the only frame watched, every frame is watched when `None`
+    pub frame: Option<usize>,
+}
+
+impl FromStr for Watchpoint {
+    type Err = eyre::Error;
+
+    /// Accepts a word like `3`, an inclusive range of words like `2-5`, or `fmp` for the word
+    /// holding the free memory pointer
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim().to_lowercase();
+        let (first, last) = match text.split_once('-') {
+            _ if text == "fmp" => (FREE_MEMORY_POINTER_WORD, FREE_MEMORY_POINTER_WORD),
+            Some((first, last)) => {
+                let (first, last) = (parse_number(first)?, parse_number(last)?);
+                if last < first {
+                    return Err(eyre!("Word range {} ends before it starts", text));
+                }
+                (first as usize, last as usize)
+            }
+            None => {
+                let word = parse_number(&text)? as usize;
+                (word, word)
+            }
+        };
+        let end = last.checked_add(1).ok_or_else(|| eyre!("Word {} is out of range", last))?;
+        Ok(Self { words: first..end, frame: None })
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.words.len() {
+            1 => write!(f, "word {}", self.words.start)?,
+            _ => write!(f, "words {}-{}", self.words.start, self.words.end - 1)?,
+        }
+        match self.frame {
+            Some(frame) => write!(f, " of call {}", frame),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Watchpoint {
+    /// Watches the words starting at the address the free memory pointer of the current frame
+    /// holds, which is where the next allocation goes
+    pub fn free_memory(
+        transaction_state: &TransactionState,
+        words: usize,
+    ) -> Result<Self, eyre::Error> {
+        let step = transaction_state
+            .next_operation
+            .checked_sub(1)
+            .and_then(|operation| transaction_state.raw_data.get(operation as usize))
+            .ok_or_else(|| eyre!("No operation has run yet"))?;
+        let pointer = step
+            .memory
+            .as_ref()
+            .and_then(|memory| memory.get(FREE_MEMORY_POINTER_WORD))
+            .and_then(|word| U256::from_str_radix(word, 16).ok())
+            .ok_or_else(|| eyre!("The free memory pointer is not set in this frame"))?;
+        let first = pointer.saturating_to::<usize>() / 32;
+        let end = first
+            .checked_add(words.max(1))
+            .ok_or_else(|| eyre!("{} words past word {} are out of range", words, first))?;
+        Ok(Self { words: first..end, frame: None })
+    }
+
+    /// Whether the watchpoint applies to the frame of the given call tree node
+    pub fn watches(&self, frame: usize) -> bool {
+        self.frame.map_or(true, |watched| watched == frame)
+    }
+
+    /// How the given operation touches the watched words, if it does
+    pub fn hit(&self, transaction_state: &TransactionState, operation: usize) -> SlotStatus {
+        let Some(step) = transaction_state.raw_data.get(operation) else {
+            return SlotStatus::Empty;
+        };
+        if !self.watches(transaction_state.call_tree.node_at(operation, step.depth)) {
+            return SlotStatus::Empty;
+        }
+        let stack = step.stack.as_deref().unwrap_or_default();
+        let memory_size = step.memory.as_ref().map_or(0, Vec::len) * 32;
+        let access = MemoryAccess::from_operation(&step.op, stack, memory_size);
+        let overlaps = |ranges: &[Range<usize>]| {
+            ranges.iter().map(access::slots).any(|slots| {
+                slots.start < self.words.end && self.words.start < slots.end
+            })
+        };
+        // Writes take precedence like they do for slot statuses
+        if overlaps(&access.writes) {
+            SlotStatus::Writing
+        } else if overlaps(&access.reads) {
+            SlotStatus::Reading
+        } else {
+            SlotStatus::Empty
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_utils::{step, struct_logs};
+
+    #[test]
+    fn words_past_the_address_space_are_refused() {
+        assert_eq!("2-5".parse::<Watchpoint>().unwrap().words, 2..6);
+        assert_eq!("fmp".parse::<Watchpoint>().unwrap().words, 2..3);
+        assert!("18446744073709551615".parse::<Watchpoint>().is_err());
+        assert!("0-0xffffffffffffffff".parse::<Watchpoint>().is_err());
+    }
+
+    #[test]
+    fn free_memory_counts_past_the_address_space_are_refused() {
+        let mut memory = vec!["00".repeat(32); 3];
+        memory[FREE_MEMORY_POINTER_WORD] = format!("{:064x}", 0x80);
+        let mut step = step(1, "MLOAD", &[0x40]);
+        step["memory"] = json!(memory);
+        let state = TransactionState {
+            raw_data: struct_logs(vec![step]),
+            next_operation: 1,
+            ..Default::default()
+        };
+        assert_eq!(Watchpoint::free_memory(&state, 2).unwrap().words, 4..6);
+        assert!(Watchpoint::free_memory(&state, usize::MAX).is_err());
+    }
+}